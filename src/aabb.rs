@@ -0,0 +1,74 @@
+use crate::vector3::Vector3;
+use crate::ray::Ray;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Aabb {
+    minimum: Vector3,
+    maximum: Vector3,
+}
+
+impl Aabb {
+    pub fn new(a: Vector3, b: Vector3) -> Self {
+        // Treat the two points as extrema, so the corners can be given in any order.
+        let minimum = Vector3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+        let maximum = Vector3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+        Aabb { minimum, maximum }.pad_to_minimums()
+    }
+    pub fn min(&self) -> Vector3 { self.minimum }
+    pub fn max(&self) -> Vector3 { self.maximum }
+
+    pub fn surrounding_box(box0: &Aabb, box1: &Aabb) -> Aabb {
+        Aabb::new(
+            Vector3::new(box0.minimum.x().min(box1.minimum.x()), box0.minimum.y().min(box1.minimum.y()), box0.minimum.z().min(box1.minimum.z())),
+            Vector3::new(box0.maximum.x().max(box1.maximum.x()), box0.maximum.y().max(box1.maximum.y()), box0.maximum.z().max(box1.maximum.z())),
+        )
+    }
+
+    pub fn centroid(&self) -> Vector3 { 0.5 * (self.minimum + self.maximum) }
+
+    pub fn surface_area(&self) -> f64 {
+        let d = self.maximum - self.minimum;
+        2.0 * (d.x() * d.y() + d.y() * d.z() + d.z() * d.x())
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let d = self.maximum - self.minimum;
+        if d.x() > d.y() && d.x() > d.z() { 0 } else if d.y() > d.z() { 1 } else { 2 }
+    }
+
+    pub fn hit(&self, r: &Ray, mut t_min: f64, mut t_max: f64) -> bool {
+        let orig = r.orig();
+        let dir = r.dir();
+        for axis in 0..3 {
+            let inv_d = 1.0 / dir[axis];
+            let mut t0 = (self.minimum[axis] - orig[axis]) * inv_d;
+            let mut t1 = (self.maximum[axis] - orig[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max <= t_min {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn pad_to_minimums(self) -> Aabb {
+        // Give flat boxes (e.g. around a quad) a small thickness so rays never miss them.
+        let delta = 0.0001;
+        let mut minimum = [self.minimum.x(), self.minimum.y(), self.minimum.z()];
+        let mut maximum = [self.maximum.x(), self.maximum.y(), self.maximum.z()];
+        for axis in 0..3 {
+            if maximum[axis] - minimum[axis] < delta {
+                minimum[axis] -= delta / 2.0;
+                maximum[axis] += delta / 2.0;
+            }
+        }
+        Aabb {
+            minimum: Vector3::new(minimum[0], minimum[1], minimum[2]),
+            maximum: Vector3::new(maximum[0], maximum[1], maximum[2]),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::vector3::Vector3;
+
+pub trait Background: Send + Sync {
+    fn value(&self, r: &Ray) -> Vector3;
+}
+
+pub struct SolidBackground {
+    color: Vector3
+}
+
+impl SolidBackground {
+    pub fn new(color: Vector3) -> Self { SolidBackground { color } }
+}
+
+impl Background for SolidBackground {
+    fn value(&self, _r: &Ray) -> Vector3 {
+        self.color
+    }
+}
+
+pub struct GradientBackground {
+    bottom: Vector3,
+    top: Vector3
+}
+
+impl GradientBackground {
+    pub fn new(bottom: Vector3, top: Vector3) -> Self { GradientBackground { bottom, top } }
+}
+
+impl Default for GradientBackground {
+    fn default() -> Self { GradientBackground::new(Vector3::new(1.0, 1.0, 1.0), Vector3::new(0.5, 0.7, 1.0)) }
+}
+
+impl Background for GradientBackground {
+    fn value(&self, r: &Ray) -> Vector3 {
+        let unit_direction = r.dir().unit_vector();
+        let a = 0.5 * (unit_direction.y() + 1.0);
+        self.bottom * (1.0 - a) + self.top * a
+    }
+}
+
+pub struct EnvironmentBackground {
+    texture: Arc<dyn Texture>
+}
+
+impl EnvironmentBackground {
+    pub fn new(texture: Arc<dyn Texture>) -> Self { EnvironmentBackground { texture } }
+}
+
+impl Background for EnvironmentBackground {
+    fn value(&self, r: &Ray) -> Vector3 {
+        // Look the direction up as a point on the unit sphere, using the same (u, v)
+        // mapping as Sphere so equirectangular images line up the same way.
+        let d = r.dir().unit_vector();
+        let theta = (-d.y()).acos();
+        let phi = (-d.z()).atan2(d.x()) + PI;
+        self.texture.value(phi / (2.0 * PI), theta / PI, &d)
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable, HittableList};
+
+pub struct BvhNode {
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    bbox: Aabb,
+}
+
+impl BvhNode {
+    // None if the list is empty or holds an object without a bounding box, such as an empty
+    // list, in which case the caller has to intersect the objects linearly.
+    pub fn new(list: &HittableList, time0: f64, time1: f64) -> Option<Self> {
+        let objects = list.objects().iter()
+            .map(|object| {
+                let mut bbox = Aabb::default();
+                object.bounding_box(time0, time1, &mut bbox).then(|| (object.clone(), bbox))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        if objects.is_empty() {
+            return None;
+        }
+        Some(BvhNode::from_objects(objects))
+    }
+
+    fn from_objects(mut objects: Vec<(Arc<dyn Hittable>, Aabb)>) -> Self {
+        match objects.len() {
+            1 => {
+                let (object, bbox) = objects.pop().unwrap();
+                BvhNode { left: object.clone(), right: object, bbox }
+            }
+            2 => {
+                let (right, right_box) = objects.pop().unwrap();
+                let (left, left_box) = objects.pop().unwrap();
+                BvhNode { left, right, bbox: Aabb::surrounding_box(&left_box, &right_box) }
+            }
+            _ => {
+                let (axis, split) = BvhNode::sah_split(&mut objects);
+                sort_by_axis(&mut objects, axis);
+                let right_objects = objects.split_off(split);
+                let bbox = objects.iter().chain(right_objects.iter())
+                    .fold(objects[0].1, |acc, (_, b)| Aabb::surrounding_box(&acc, b));
+                let left = BvhNode::subtree(objects);
+                let right = BvhNode::subtree(right_objects);
+                BvhNode { left, right, bbox }
+            }
+        }
+    }
+
+    fn subtree(mut objects: Vec<(Arc<dyn Hittable>, Aabb)>) -> Arc<dyn Hittable> {
+        // Single objects go directly into the parent instead of getting a node of their own.
+        if objects.len() == 1 {
+            return objects.pop().unwrap().0;
+        }
+        Arc::new(BvhNode::from_objects(objects))
+    }
+
+    fn sah_split(objects: &mut [(Arc<dyn Hittable>, Aabb)]) -> (usize, usize) {
+        // Surface area heuristic: choose the axis and partition that minimize the expected cost
+        // of intersecting both children, i.e. sum over children of area * number of objects.
+        let n = objects.len();
+        let mut best = (0, n / 2);
+        let mut best_cost = f64::INFINITY;
+
+        for axis in 0..3 {
+            sort_by_axis(objects, axis);
+
+            let mut left_areas = vec![0.0; n];
+            let mut running = objects[0].1;
+            for i in 0..n {
+                running = Aabb::surrounding_box(&running, &objects[i].1);
+                left_areas[i] = running.surface_area();
+            }
+
+            let mut running = objects[n - 1].1;
+            for i in (1..n).rev() {
+                running = Aabb::surrounding_box(&running, &objects[i].1);
+                let cost = (i as f64) * left_areas[i - 1] + ((n - i) as f64) * running.surface_area();
+                if cost < best_cost {
+                    best_cost = cost;
+                    best = (axis, i);
+                }
+            }
+        }
+
+        best
+    }
+}
+
+fn sort_by_axis(objects: &mut [(Arc<dyn Hittable>, Aabb)], axis: usize) {
+    objects.sort_by(|a, b| a.1.centroid()[axis].total_cmp(&b.1.centroid()[axis]));
+}
+
+impl Hittable for BvhNode {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        if !self.bbox.hit(r, t_min, t_max) {
+            return false;
+        }
+
+        let hit_left = self.left.hit(r, t_min, t_max, rec);
+        let hit_right = self.right.hit(r, t_min, if hit_left { rec.t } else { t_max }, rec);
+
+        hit_left || hit_right
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        *output_box = self.bbox;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+    use crate::sphere::Sphere;
+    use crate::vector3::Vector3;
+
+    fn random_vector(rng: &mut Rng, min: f64, max: f64) -> Vector3 {
+        Vector3::new(rng.random_double(min, max), rng.random_double(min, max), rng.random_double(min, max))
+    }
+
+    #[test]
+    fn rejects_lists_without_bounding_boxes() {
+        assert!(BvhNode::new(&HittableList::new(), 0.0, 0.0).is_none());
+        let mut list = HittableList::new();
+        list.push(Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.0, None));
+        list.push(HittableList::new());
+        assert!(BvhNode::new(&list, 0.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn hits_the_same_as_the_linear_list() {
+        let mut rng = Rng::new(7);
+        let mut list = HittableList::new();
+        for _ in 0..200 {
+            list.push(Sphere::new(random_vector(&mut rng, -10.0, 10.0), rng.random_double(0.1, 1.5), None));
+        }
+        let bvh = BvhNode::new(&list, 0.0, 0.0).unwrap();
+
+        let mut hits = 0;
+        for _ in 0..10_000 {
+            let ray = Ray::new(random_vector(&mut rng, -15.0, 15.0), random_vector(&mut rng, -1.0, 1.0), 0.0);
+            let (mut linear, mut tree) = (HitRecord::default(), HitRecord::default());
+            let hit = list.hit(&ray, 0.001, f64::INFINITY, &mut linear);
+            assert_eq!(bvh.hit(&ray, 0.001, f64::INFINITY, &mut tree), hit);
+            if hit {
+                hits += 1;
+                assert_eq!(tree.t, linear.t);
+                assert_eq!(tree.p, linear.p);
+                assert_eq!(tree.normal, linear.normal);
+            }
+        }
+        assert!(hits > 1000, "only {} of the rays hit a sphere", hits);
+    }
+}
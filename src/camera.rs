@@ -1,14 +1,41 @@
-use image::{RgbImage, ImageBuffer, Rgb};
-use indicatif::{ProgressBar, ProgressStyle};
-use crate::hittable::{HitRecord, Hittable};
-use crate::random_double;
+use std::f64::consts::PI;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use crate::background::{Background, GradientBackground};
+use crate::hittable::Hittable;
+use crate::framebuffer::Framebuffer;
+use crate::output::{ExrPrecision, SaveError};
+use crate::integrator::{Integrator, PathIntegrator};
+use crate::progress::{NoProgress, ProgressReporter};
+use crate::tonemap::{PostProcess, ToneMap, TransferFunction};
+use crate::sampler::{sample_concentric_disk, IndependentSampler, Sampler};
 use crate::vector3::Vector3;
 use crate::ray::Ray;
+use crate::rng::Rng;
+
+// Settings that replace the ones a scene chose for its camera. Fields left as None are kept.
+#[derive(Clone, Debug, Default)]
+pub struct RenderOverrides {
+    pub image_width: Option<u32>,
+    pub samples_per_pixel: Option<u32>,
+    pub max_depth: Option<u32>,
+    pub seed: Option<u64>,
+    pub thread_count: Option<usize>,
+    pub noise_threshold: Option<f64>,
+    pub min_samples_per_pixel: Option<u32>,
+    pub exposure: Option<f64>,
+    pub tone_map: Option<ToneMap>,
+    pub transfer: Option<TransferFunction>,
+    pub dither: Option<bool>,
+}
 
 pub struct Camera {
     pub aspect_ratio: f64,
     pub image_width: u32,
-    pub samples_per_pixel: u32,
+    pub samples_per_pixel: u32, // the maximum when sampling adaptively
     pub max_depth: u32,
     pub vfov: f64, // degrees
     pub look_from: Vector3,
@@ -21,6 +48,23 @@ pub struct Camera {
     pub time0: f64,
     pub time1: f64,
 
+    pub background: Arc<dyn Background>,
+    pub lights: Option<Arc<dyn Hittable>>,
+    pub integrator: Arc<dyn Integrator>,
+    pub sampler: Arc<dyn Sampler>,
+
+    pub seed: u64,
+    pub thread_count: usize, // 0 uses every available core
+    pub tile_size: u32,
+    pub progress: Arc<dyn ProgressReporter>,
+    pub post_process: PostProcess, // applies to 8-bit output only
+
+    // Adaptive sampling stops taking samples for a pixel once the 95% confidence interval of its
+    // luminance is within noise_threshold of the mean, relative to the mean. A threshold of 0
+    // gives every pixel samples_per_pixel samples.
+    pub noise_threshold: f64,
+    pub min_samples_per_pixel: u32,
+
     pub image_height: u32,
     pub pixel_samples_scale: f64,
     pub pixel00_loc: Vector3,
@@ -32,6 +76,7 @@ pub struct Camera {
 
 impl Camera {
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(aspect_ratio: f64, image_width: u32, samples_per_pixel: u32, max_depth: u32, vfov: f64, look_from: Vector3, look_at: Vector3, v_up: Vector3, defocus_angle: f64, focus_dist: f64, time0: f64, time1: f64) -> Self {
         let mut camera = Camera {
             aspect_ratio,
@@ -46,6 +91,17 @@ impl Camera {
             focus_dist,
             time0,
             time1,
+            background: Arc::new(GradientBackground::default()),
+            lights: None,
+            integrator: Arc::new(PathIntegrator),
+            sampler: Arc::new(IndependentSampler::default()),
+            seed: 0,
+            thread_count: 0,
+            tile_size: 16,
+            progress: Arc::new(NoProgress),
+            post_process: PostProcess::default(),
+            noise_threshold: 0.0,
+            min_samples_per_pixel: 16,
             image_height: 0,
             pixel_samples_scale: 1.0,
             pixel00_loc: Vector3::new(0.0, 0.0, 0.0),
@@ -58,6 +114,21 @@ impl Camera {
         camera
     }
 
+    pub fn apply_overrides(&mut self, overrides: &RenderOverrides) {
+        if let Some(image_width) = overrides.image_width { self.image_width = image_width; }
+        if let Some(samples_per_pixel) = overrides.samples_per_pixel { self.samples_per_pixel = samples_per_pixel; }
+        if let Some(max_depth) = overrides.max_depth { self.max_depth = max_depth; }
+        if let Some(seed) = overrides.seed { self.seed = seed; }
+        if let Some(thread_count) = overrides.thread_count { self.thread_count = thread_count; }
+        if let Some(noise_threshold) = overrides.noise_threshold { self.noise_threshold = noise_threshold; }
+        if let Some(min_samples) = overrides.min_samples_per_pixel { self.min_samples_per_pixel = min_samples; }
+        if let Some(exposure) = overrides.exposure { self.post_process.exposure = exposure; }
+        if let Some(tone_map) = overrides.tone_map { self.post_process.tone_map = tone_map; }
+        if let Some(transfer) = overrides.transfer { self.post_process.transfer = transfer; }
+        if let Some(dither) = overrides.dither { self.post_process.dither = dither; }
+        self.initialize();
+    }
+
     pub fn initialize(&mut self) {
 
         self.image_height = (self.image_width as f64 / self.aspect_ratio) as u32;
@@ -66,7 +137,7 @@ impl Camera {
         self.pixel_samples_scale = 1.0 / (self.samples_per_pixel as f64);
 
         // Determine viewport dimensions.
-        let theta = 3.141592653589793 * self.vfov / 180.0;
+        let theta = PI * self.vfov / 180.0;
         let h = (theta / 2.0).tan();
         let viewport_height = 2.0 * h * self.focus_dist;
         let viewport_width = viewport_height * (self.image_width as f64 / self.image_height as f64);
@@ -89,80 +160,172 @@ impl Camera {
         self.pixel00_loc = viewport_upper_left + (self.pixel_delta_u + self.pixel_delta_v) * 0.5;
 
         // Calculate the camera defocus disk basis vectors.
-        let defocus_radius = self.focus_dist * (3.141592653589793 * self.defocus_angle / 360.0).tan();
+        let defocus_radius = self.focus_dist * (PI * self.defocus_angle / 360.0).tan();
         self.defocus_disk_u = defocus_radius * u;
         self.defocus_disk_v = defocus_radius * v;
     }
 
-    pub fn render(&self, world: &dyn Hittable, image_name: &str) {
-        let mut buffer: RgbImage = ImageBuffer::new(self.image_width, self.image_height);
-        let intro_message = format!("Rendering {}.png:", image_name);
-        println!("{}", intro_message);
-        let bar = ProgressBar::new((self.image_width * self.image_height) as u64);
-        bar.set_style(ProgressStyle::default_bar()
-            .template("[{elapsed_precise}] [{bar:40.cyan/blue}]{pos:>7}/{len:7}\n{msg}\n")
-            .expect("Unable to create progress bar style."));
-        for (x, y, pixel) in buffer.enumerate_pixels_mut() {
-            let mut pixel_color = Vector3::new(0.0, 0.0, 0.0);
-            for _ in 0..self.samples_per_pixel {
-                let ray = self.get_ray(x, y);
-                pixel_color = pixel_color + ray_color(ray, self.max_depth, world);
+    // Renders to images/<image_name>. The file extension picks the output format, and names
+    // without one are saved as PNG.
+    pub fn render(&self, world: &dyn Hittable, image_name: &str) -> Result<(), SaveError> {
+        let file_name = if Path::new(image_name).extension().is_some() {
+            image_name.to_string()
+        } else {
+            format!("{}.png", image_name)
+        };
+        self.render_to_file(world, &Path::new("images").join(file_name), ExrPrecision::default()).map(|_| ())
+    }
+
+    // Renders and saves to path, in the format given by its extension, see output::save. The
+    // framebuffer is returned for anything else the caller wants to save from it.
+    pub fn render_to_file(&self, world: &dyn Hittable, path: &Path, exr_precision: ExrPrecision) -> Result<Framebuffer, SaveError> {
+        self.progress.message(&format!("Rendering {}:", path.display()));
+        let buffer = self.render_to_buffer(world);
+        buffer.save_with(path, &self.post_process, exr_precision)?;
+        self.progress.message(&format!("Finished rendering {}!", path.display()));
+        Ok(buffer)
+    }
+
+    pub fn render_to_buffer(&self, world: &dyn Hittable) -> Framebuffer {
+        let mut buffer = Framebuffer::new(self.image_width, self.image_height);
+        self.progress.start((self.image_width * self.image_height) as u64);
+
+        // Workers pull the next unrendered tile from a shared counter and send finished tiles
+        // back to this thread, which copies them into the buffer and reports progress.
+        let tiles = self.tiles();
+        let next_tile = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+        thread::scope(|scope| {
+            for _ in 0..self.worker_count() {
+                let sender = sender.clone();
+                let tiles = &tiles;
+                let next_tile = &next_tile;
+                scope.spawn(move || {
+                    loop {
+                        let index = next_tile.fetch_add(1, Ordering::Relaxed);
+                        let Some(&tile) = tiles.get(index) else { break };
+                        let pixels = self.render_tile(world, tile);
+                        if sender.send((tile, pixels)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(sender);
+
+            for ((x0, y0, width, height), pixels) in receiver {
+                for (i, (pixel, sample_count)) in pixels.into_iter().enumerate() {
+                    let x = x0 + i as u32 % width;
+                    let y = y0 + i as u32 / width;
+                    buffer.set_pixel(x, y, pixel);
+                    buffer.set_sample_count(x, y, sample_count);
+                }
+                self.progress.advance((width * height) as u64);
             }
-            pixel_color = self.pixel_samples_scale * pixel_color;
-            let ir = (255.999 * pixel_color.x().sqrt()) as u8;
-            let ig = (255.999 * pixel_color.y().sqrt()) as u8;
-            let ib = (255.999 * pixel_color.z().sqrt()) as u8;
+        });
 
-            *pixel = Rgb([ir, ig, ib]);
+        self.progress.finish();
+        buffer
+    }
 
-            bar.inc(1);
+    fn worker_count(&self) -> usize {
+        if self.thread_count > 0 {
+            self.thread_count
+        } else {
+            thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
         }
-        let finish_message = format!("Finished rendering {}.png!", image_name);
-        bar.finish_with_message(finish_message);
+    }
 
-        let image_path = format!("images/{}.png", image_name);
-        buffer.save(image_path).unwrap();
+    fn tiles(&self) -> Vec<(u32, u32, u32, u32)> {
+        let tile_size = self.tile_size.max(1);
+        let mut tiles = Vec::new();
+        for y0 in (0..self.image_height).step_by(tile_size as usize) {
+            for x0 in (0..self.image_width).step_by(tile_size as usize) {
+                let width = tile_size.min(self.image_width - x0);
+                let height = tile_size.min(self.image_height - y0);
+                tiles.push((x0, y0, width, height));
+            }
+        }
+        tiles
     }
 
-    fn get_ray(&self, i: u32, j: u32) -> Ray {
-        // Construct a camera ray originating from the defocus disk and directed at a randomly
-        // sampled point around the pixel location i, j.
-        let pixel_sample = self.pixel00_loc
-                         + ((i as f64 + random_double(-0.5, 0.5)) * self.pixel_delta_u)
-                         + ((j as f64 + random_double(-0.5, 0.5)) * self.pixel_delta_v);
-        let ray_origin = if self.defocus_angle <= 0.0 { self.look_from } else { self.defocus_disk_sample() };
-        let ray_direction = pixel_sample - ray_origin;
-        let time = if self.time0 == self.time1 { self.time0 } else { random_double(self.time0, self.time1) };
-        Ray::new(ray_origin, ray_direction, time)
+    // Returns the color and number of samples taken for each pixel of the tile.
+    fn render_tile(&self, world: &dyn Hittable, (x0, y0, width, height): (u32, u32, u32, u32)) -> Vec<(Vector3, u32)> {
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        let mut sampler = self.sampler.for_render(self.samples_per_pixel, self.seed);
+        for y in y0..y0 + height {
+            for x in x0..x0 + width {
+                if self.noise_threshold <= 0.0 {
+                    let mut pixel_color = Vector3::new(0.0, 0.0, 0.0);
+                    for sample_index in 0..self.samples_per_pixel {
+                        pixel_color = pixel_color + self.sample_pixel(world, x, y, sample_index, sampler.as_mut());
+                    }
+                    pixels.push((self.pixel_samples_scale * pixel_color, self.samples_per_pixel));
+                } else {
+                    pixels.push(self.render_pixel_adaptive(world, x, y, sampler.as_mut()));
+                }
+            }
+        }
+        pixels
     }
 
-    fn defocus_disk_sample(&self) -> Vector3 {
-        let p = Vector3::random_in_unit_disk();
-        self.look_from + (p.x() * self.defocus_disk_u) + (p.y() * self.defocus_disk_v)
+    fn sample_pixel(&self, world: &dyn Hittable, x: u32, y: u32, sample_index: u32, sampler: &mut dyn Sampler) -> Vector3 {
+        sampler.start_pixel_sample(x, y, sample_index);
+        let key = Rng::from_keys(&[self.seed, x as u64, y as u64, sample_index as u64]).next_u64();
+        let ray = self.get_ray(x, y, sampler).with_key(key);
+        self.integrator.ray_color(ray, world, self, sampler)
     }
 
-}
+    fn render_pixel_adaptive(&self, world: &dyn Hittable, x: u32, y: u32, sampler: &mut dyn Sampler) -> (Vector3, u32) {
+        let min_samples = self.min_samples_per_pixel.clamp(2, self.samples_per_pixel.max(2));
+        let mut pixel_color = Vector3::new(0.0, 0.0, 0.0);
+        // Welford's running mean and sum of squared deviations of the samples' luminance.
+        let mut mean = 0.0;
+        let mut m2 = 0.0;
+        let mut n = 0;
 
-fn ray_color(r: Ray, depth: u32, world: &dyn Hittable) -> Vector3 {
-    if depth <= 0 {
-        return Vector3::new(0.0, 0.0, 0.0);
-    }
-    
-    let mut rec = HitRecord::default();
-    if world.hit(&r, 0.001, f64::INFINITY, &mut rec) {
-
-        let mut scattered = Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0), r.time());
-        let mut attenuation = Vector3::new(0.0, 0.0, 0.0);
-        
-        if let Some(mat) = &rec.mat {
-            if mat.scatter(&r, &rec, &mut attenuation, &mut scattered) {
-                return attenuation * ray_color(scattered, depth-1, world);
+        while n < self.samples_per_pixel {
+            let sample = self.sample_pixel(world, x, y, n, sampler);
+            pixel_color = pixel_color + sample;
+
+            n += 1;
+            let luminance = 0.2126 * sample.x() + 0.7152 * sample.y() + 0.0722 * sample.z();
+            let delta = luminance - mean;
+            mean += delta / n as f64;
+            m2 += delta * (luminance - mean);
+
+            if n >= min_samples {
+                let variance_of_mean = m2 / ((n - 1) as f64 * n as f64);
+                let half_width = 1.96 * variance_of_mean.sqrt();
+                if half_width <= self.noise_threshold * mean.max(1e-3) {
+                    break;
+                }
             }
         }
-        return Vector3::new(0.0, 0.0, 0.0);
+
+        (pixel_color / n.max(1) as f64, n)
+    }
+
+    fn get_ray(&self, i: u32, j: u32, sampler: &mut dyn Sampler) -> Ray {
+        // Construct a camera ray originating from the defocus disk and directed at a sampled
+        // point around the pixel location i, j. The lens and time dimensions are always drawn
+        // so that integrators start from the same dimension for every camera.
+        let (offset_u, offset_v) = sampler.get_pixel_2d();
+        let lens_sample = sampler.get_2d();
+        let time_sample = sampler.get_1d();
+
+        let pixel_sample = self.pixel00_loc
+                         + ((i as f64 + offset_u - 0.5) * self.pixel_delta_u)
+                         + ((j as f64 + offset_v - 0.5) * self.pixel_delta_v);
+        let ray_origin = if self.defocus_angle <= 0.0 { self.look_from } else { self.defocus_disk_sample(lens_sample) };
+        let ray_direction = pixel_sample - ray_origin;
+        let time = self.time0 + time_sample * (self.time1 - self.time0);
+        Ray::new(ray_origin, ray_direction, time)
     }
 
-    let unit_direction = r.dir().unit_vector();
-    let a = 0.5 * (unit_direction.y() + 1.0);
-    return Vector3::new(1.0,1.0,1.0) * (1.0 - a) + Vector3::new(0.5,0.7,1.0) * a;
-}
\ No newline at end of file
+    fn defocus_disk_sample(&self, lens_sample: (f64, f64)) -> Vector3 {
+        let (x, y) = sample_concentric_disk(lens_sample);
+        self.look_from + (x * self.defocus_disk_u) + (y * self.defocus_disk_v)
+    }
+
+}
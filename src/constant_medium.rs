@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use crate::vector3::Vector3;
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::{Isotropic, Material};
+use crate::texture::Texture;
+use crate::aabb::Aabb;
+use crate::rng::Rng;
+use crate::sampler::Sampler;
+
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    neg_inv_density: f64,
+    phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Arc<dyn Hittable>, density: f64, albedo: Vector3) -> Self {
+        ConstantMedium { boundary, neg_inv_density: -1.0 / density, phase_function: Arc::new(Isotropic::new(albedo)) }
+    }
+    pub fn new_from_texture(boundary: Arc<dyn Hittable>, density: f64, texture: Arc<dyn Texture>) -> Self {
+        ConstantMedium { boundary, neg_inv_density: -1.0 / density, phase_function: Arc::new(Isotropic::new_from_texture(texture)) }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        // Find where the ray enters and leaves the boundary. Searching the whole line handles
+        // rays that start inside the volume, whose entry point lies behind the origin.
+        let mut rec1 = HitRecord::default();
+        let mut rec2 = HitRecord::default();
+
+        if !self.boundary.hit(r, f64::NEG_INFINITY, f64::INFINITY, &mut rec1) {
+            return false;
+        }
+        if !self.boundary.hit(r, rec1.t + 0.0001, f64::INFINITY, &mut rec2) {
+            return false;
+        }
+
+        rec1.t = rec1.t.max(t_min);
+        rec2.t = rec2.t.min(t_max);
+        if rec1.t >= rec2.t {
+            return false;
+        }
+        rec1.t = rec1.t.max(0.0);
+
+        // Sample the free-flight distance. Hit tests take no sampler, so that the dimensions a path
+        // uses don't depend on how many objects it is tested against; instead the random number
+        // comes from a stream keyed on the ray's pixel sample and on the ray itself, which tells
+        // apart the bounces and shadow rays of a path.
+        let mut rng = Rng::from_keys(&[
+            r.key(),
+            r.orig().x().to_bits(), r.orig().y().to_bits(), r.orig().z().to_bits(),
+            r.dir().x().to_bits(), r.dir().y().to_bits(), r.dir().z().to_bits(),
+        ]);
+        let ray_length = r.dir().length();
+        let distance_inside_boundary = (rec2.t - rec1.t) * ray_length;
+        let hit_distance = self.neg_inv_density * (1.0 - rng.random_double(0.0, 1.0)).ln();
+
+        if hit_distance > distance_inside_boundary {
+            return false;
+        }
+
+        rec.t = rec1.t + hit_distance / ray_length;
+        rec.p = r.at(rec.t);
+        rec.normal = Vector3::new(1.0, 0.0, 0.0); // arbitrary
+        rec.front_face = true;                     // also arbitrary
+        rec.tangent = Vector3::new(0.0, 0.0, 0.0);
+        rec.u = 0.0;
+        rec.v = 0.0;
+        rec.mat = Some(self.phase_function.clone());
+
+        true
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+        self.boundary.bounding_box(time0, time1, output_box)
+    }
+
+    // Sampled as a light through its boundary, the directions that can reach the volume.
+    fn pdf_value(&self, origin: Vector3, direction: Vector3) -> f64 {
+        self.boundary.pdf_value(origin, direction)
+    }
+
+    fn random(&self, origin: Vector3, sampler: &mut dyn Sampler) -> Vector3 {
+        self.boundary.random(origin, sampler)
+    }
+}
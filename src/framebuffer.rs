@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use image::{ImageError, RgbImage};
+
+use crate::output::{self, ExrPrecision, SaveError};
+use crate::tonemap::PostProcess;
+use crate::vector3::Vector3;
+
+// Linear, high dynamic range RGB pixels as produced by the renderer, stored row by row from
+// the top-left corner, along with the number of samples each pixel received.
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 3]>,
+    sample_counts: Vec<u32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+        Framebuffer { width, height, pixels: vec![[0.0; 3]; size], sample_counts: vec![0; size] }
+    }
+    pub fn width(&self) -> u32 { self.width }
+    pub fn height(&self) -> u32 { self.height }
+    pub fn pixels(&self) -> &[[f32; 3]] { &self.pixels }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Vector3 {
+        let [r, g, b] = self.pixels[self.index(x, y)];
+        Vector3::new(r as f64, g as f64, b as f64)
+    }
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Vector3) {
+        let index = self.index(x, y);
+        self.pixels[index] = [color.x() as f32, color.y() as f32, color.z() as f32];
+    }
+
+    pub fn sample_count(&self, x: u32, y: u32) -> u32 {
+        self.sample_counts[self.index(x, y)]
+    }
+    pub fn set_sample_count(&mut self, x: u32, y: u32, count: u32) {
+        let index = self.index(x, y);
+        self.sample_counts[index] = count;
+    }
+
+    // False color image of the sample counts, going from blue for the fewest samples through
+    // green and yellow to red for the most.
+    pub fn sample_heatmap(&self) -> Framebuffer {
+        let min = self.sample_counts.iter().copied().min().unwrap_or(0);
+        let max = self.sample_counts.iter().copied().max().unwrap_or(0);
+        let mut heatmap = Framebuffer::new(self.width, self.height);
+        heatmap.sample_counts.clone_from(&self.sample_counts);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let t = if max > min { (self.sample_count(x, y) - min) as f64 / (max - min) as f64 } else { 0.0 };
+                heatmap.set_pixel(x, y, heat_color(t));
+            }
+        }
+        heatmap
+    }
+
+    // Encode to 8 bits per channel with the default post-process: clipped at 1.0, sRGB curve.
+    pub fn to_rgb_image(&self) -> RgbImage {
+        self.to_rgb_image_with(&PostProcess::default())
+    }
+    pub fn to_rgb_image_with(&self, post: &PostProcess) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |x, y| post.to_rgb8(self.get_pixel(x, y), x, y))
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), ImageError> {
+        self.to_rgb_image().save(path)
+    }
+
+    // Saves in the format given by the file extension, see output::save.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        output::save(self, path.as_ref(), &PostProcess::default(), ExrPrecision::default())
+    }
+    pub fn save_with(&self, path: impl AsRef<Path>, post: &PostProcess, exr_precision: ExrPrecision) -> Result<(), SaveError> {
+        output::save(self, path.as_ref(), post, exr_precision)
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+}
+
+fn heat_color(t: f64) -> Vector3 {
+    let stops = [
+        Vector3::new(0.0, 0.0, 1.0),
+        Vector3::new(0.0, 1.0, 1.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        Vector3::new(1.0, 1.0, 0.0),
+        Vector3::new(1.0, 0.0, 0.0),
+    ];
+    let scaled = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+    let i = (scaled as usize).min(stops.len() - 2);
+    let f = scaled - i as f64;
+    (1.0 - f) * stops[i] + f * stops[i + 1]
+}
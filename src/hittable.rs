@@ -1,13 +1,19 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 
 use crate::vector3::Vector3;
 use crate::ray::Ray;
 use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::sampler::{sample_uniform_sphere, Sampler};
 
 #[derive(Clone, Default)]
 pub struct HitRecord {
     pub p: Vector3,
     pub normal: Vector3,
+    // Direction along the surface in which u increases (dp/du), of unit length, or zero where the
+    // surface doesn't define one. Anisotropic materials orient their shading frame with it.
+    pub tangent: Vector3,
     pub mat: Option<Arc<dyn Material>>,
     pub t: f64,
     pub u: f64,
@@ -18,23 +24,42 @@ pub struct HitRecord {
 impl HitRecord {
     pub fn set_face_normal(&mut self, r: &Ray, outward_normal: &Vector3) {
         self.front_face = r.dir().dot(*outward_normal) < 0.0;
-        self.normal = if self.front_face { *outward_normal } else { -1.0 * *outward_normal };
+        self.normal = if self.front_face { *outward_normal } else { -*outward_normal };
     }
 }
 
-pub trait Hittable {
+pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool;
+    fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool;
+
+    // Density, with respect to solid angle at origin, of random() producing direction, for
+    // objects sampled as lights. Shapes that know where they are override both to aim at
+    // themselves; the defaults sample every direction uniformly, which is unbiased but wastes
+    // most samples on directions that miss the object.
+    fn pdf_value(&self, _origin: Vector3, _direction: Vector3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+    fn random(&self, _origin: Vector3, sampler: &mut dyn Sampler) -> Vector3 {
+        sample_uniform_sphere(sampler.get_2d())
+    }
 }
 
+#[derive(Clone, Default)]
 pub struct HittableList {
-    list: Vec<Box<dyn Hittable>>
+    list: Vec<Arc<dyn Hittable>>
 }
 
 impl HittableList {
     pub fn new() -> Self { HittableList { list: Vec::new() } }
     pub fn push(&mut self, hittable: impl Hittable + 'static) {
-        self.list.push(Box::new(hittable))
+        self.list.push(Arc::new(hittable))
+    }
+    pub fn push_arc(&mut self, hittable: Arc<dyn Hittable>) {
+        self.list.push(hittable)
     }
+    pub fn objects(&self) -> &[Arc<dyn Hittable>] { &self.list }
+    pub fn len(&self) -> usize { self.list.len() }
+    pub fn is_empty(&self) -> bool { self.list.is_empty() }
 }
 
 impl Hittable for HittableList {
@@ -53,4 +78,37 @@ impl Hittable for HittableList {
 
         hit_anything
     }
+
+    fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+        let mut first_box = true;
+        let mut temp_box = Aabb::default();
+
+        for object in &self.list {
+            if !object.bounding_box(time0, time1, &mut temp_box) {
+                return false;
+            }
+            *output_box = if first_box { temp_box } else { Aabb::surrounding_box(output_box, &temp_box) };
+            first_box = false;
+        }
+
+        !first_box
+    }
+
+    fn pdf_value(&self, origin: Vector3, direction: Vector3) -> f64 {
+        // An empty list never produces a direction, so its density is zero everywhere.
+        if self.list.is_empty() {
+            return 0.0;
+        }
+        let weight = 1.0 / self.list.len() as f64;
+        self.list.iter().map(|object| weight * object.pdf_value(origin, direction)).sum()
+    }
+
+    fn random(&self, origin: Vector3, sampler: &mut dyn Sampler) -> Vector3 {
+        // Any direction will do for an empty list, as pdf_value gives it zero density.
+        if self.list.is_empty() {
+            return Vector3::new(1.0, 0.0, 0.0);
+        }
+        let index = ((sampler.get_1d() * self.list.len() as f64) as usize).min(self.list.len() - 1);
+        self.list[index].random(origin, sampler)
+    }
 }
\ No newline at end of file
@@ -0,0 +1,354 @@
+use std::ops::{Add, Div, Mul};
+use std::sync::Arc;
+
+use crate::camera::Camera;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::ScatterRecord;
+use crate::onb::Onb;
+use crate::pdf::{HittablePdf, MixturePdf, Pdf};
+use crate::ray::Ray;
+use crate::sampler::{sample_cosine_hemisphere, Sampler};
+use crate::spectrum::{SampledSpectrum, SampledWavelengths};
+use crate::vector3::Vector3;
+
+// Estimates the color seen along a camera ray. The camera supplies the render settings
+// (max_depth, lights and background) that integrators may use.
+pub trait Integrator: Send + Sync {
+    fn ray_color(&self, r: Ray, world: &dyn Hittable, camera: &Camera, sampler: &mut dyn Sampler) -> Vector3;
+}
+
+// Path tracer that mixes light and material sampling 50/50, with a fixed depth cutoff.
+pub struct PathIntegrator;
+
+impl Integrator for PathIntegrator {
+    fn ray_color(&self, r: Ray, world: &dyn Hittable, camera: &Camera, sampler: &mut dyn Sampler) -> Vector3 {
+        let mut radiance = Vector3::new(0.0, 0.0, 0.0);
+        let mut throughput = Vector3::new(1.0, 1.0, 1.0);
+        let mut ray = r;
+
+        for _ in 0..camera.max_depth {
+            let mut rec = HitRecord::default();
+            if !world.hit(&ray, 0.001, f64::INFINITY, &mut rec) {
+                radiance = radiance + throughput * camera.background.value(&ray);
+                break;
+            }
+
+            let Some(mat) = rec.mat.clone() else { break };
+
+            let mut srec = ScatterRecord::default();
+            radiance = radiance + throughput * mat.emitted(rec.u, rec.v, &rec.p);
+
+            if !mat.scatter(&ray, &rec, &mut srec, sampler) {
+                break;
+            }
+
+            if srec.skip_pdf {
+                throughput = throughput * srec.attenuation;
+                ray = srec.skip_pdf_ray.with_key(r.key());
+                continue;
+            }
+
+            // Sample half of the directions toward the lights and half from the material.
+            let Some(surface_pdf) = srec.pdf else { break };
+            let pdf: Arc<dyn Pdf> = match &camera.lights {
+                Some(lights) => Arc::new(MixturePdf::new(Arc::new(HittablePdf::new(lights.clone(), rec.p)), surface_pdf)),
+                None => surface_pdf,
+            };
+
+            let scattered = Ray::new(rec.p, pdf.generate(sampler), ray.time()).with_key(r.key());
+            let pdf_value = pdf.value(scattered.dir());
+            if pdf_value <= 0.0 {
+                break;
+            }
+
+            throughput = throughput * mat.eval(&ray, &rec, &scattered) / pdf_value;
+            ray = scattered;
+        }
+
+        radiance
+    }
+}
+
+// Path tracer combining next event estimation with BSDF sampling using the power heuristic,
+// terminating paths with Russian roulette. Emissive objects should be part of the camera's
+// lights so that both strategies can find them; emission from anything else is only picked up
+// by BSDF sampling. max_depth is kept as an upper bound on path length, but paths normally end
+// through Russian roulette well before it.
+pub struct MisIntegrator {
+    // Number of bounces before Russian roulette may terminate a path.
+    pub roulette_start_depth: u32,
+}
+
+impl Default for MisIntegrator {
+    fn default() -> Self { MisIntegrator { roulette_start_depth: 3 } }
+}
+
+impl Integrator for MisIntegrator {
+    fn ray_color(&self, r: Ray, world: &dyn Hittable, camera: &Camera, sampler: &mut dyn Sampler) -> Vector3 {
+        mis_ray_color(self.roulette_start_depth, r, world, camera, sampler, &mut RgbColors)
+    }
+}
+
+// Spectral version of the MIS path tracer, for effects such as dispersion that RGB can't
+// capture. Each path carries a hero wavelength and others spaced evenly from it; colors are
+// upsampled to spectra at those wavelengths as the path goes, and the radiance is converted
+// back to RGB at the end.
+pub struct SpectralIntegrator {
+    // Number of bounces before Russian roulette may terminate a path.
+    pub roulette_start_depth: u32,
+}
+
+impl Default for SpectralIntegrator {
+    fn default() -> Self { SpectralIntegrator { roulette_start_depth: 3 } }
+}
+
+impl Integrator for SpectralIntegrator {
+    fn ray_color(&self, r: Ray, world: &dyn Hittable, camera: &Camera, sampler: &mut dyn Sampler) -> Vector3 {
+        let mut wavelengths = SampledWavelengths::sample_visible(sampler.get_1d());
+        let radiance = mis_ray_color(self.roulette_start_depth, r, world, camera, sampler, &mut wavelengths);
+        radiance.to_rgb(&wavelengths)
+    }
+}
+
+// How the MIS path loop represents colors: as RGB, or as spectra at the wavelengths of a path.
+trait PathColors {
+    type Color: Copy
+        + Add<Output = Self::Color>
+        + Mul<Output = Self::Color>
+        + Mul<f64, Output = Self::Color>
+        + Div<f64, Output = Self::Color>;
+
+    fn constant(&self, value: f64) -> Self::Color;
+    // An RGB reflectance or other unitless color, such as an albedo or a BSDF value.
+    fn reflectance(&self, rgb: Vector3) -> Self::Color;
+    // An RGB emission.
+    fn illuminant(&self, rgb: Vector3) -> Self::Color;
+    fn max_value(color: Self::Color) -> f64;
+    // Wavelength for materials whose scattering depends on it, see ScatterRecord::hero_wavelength.
+    fn hero_wavelength(&self) -> Option<f64>;
+    // Called when a material set ScatterRecord::terminate_secondary_wavelengths.
+    fn terminate_secondary(&mut self);
+}
+
+struct RgbColors;
+
+impl PathColors for RgbColors {
+    type Color = Vector3;
+
+    fn constant(&self, value: f64) -> Vector3 { Vector3::new(value, value, value) }
+    fn reflectance(&self, rgb: Vector3) -> Vector3 { rgb }
+    fn illuminant(&self, rgb: Vector3) -> Vector3 { rgb }
+    fn max_value(color: Vector3) -> f64 { color.x().max(color.y()).max(color.z()) }
+    fn hero_wavelength(&self) -> Option<f64> { None }
+    fn terminate_secondary(&mut self) {}
+}
+
+impl PathColors for SampledWavelengths {
+    type Color = SampledSpectrum;
+
+    fn constant(&self, value: f64) -> SampledSpectrum { SampledSpectrum::constant(value) }
+    fn reflectance(&self, rgb: Vector3) -> SampledSpectrum { SampledSpectrum::from_rgb(rgb, self) }
+    fn illuminant(&self, rgb: Vector3) -> SampledSpectrum { SampledSpectrum::from_rgb_illuminant(rgb, self) }
+    fn max_value(color: SampledSpectrum) -> f64 { color.max_value() }
+    fn hero_wavelength(&self) -> Option<f64> { Some(self.hero()) }
+    fn terminate_secondary(&mut self) { SampledWavelengths::terminate_secondary(self) }
+}
+
+// The path loop shared by MisIntegrator and SpectralIntegrator.
+fn mis_ray_color<C: PathColors>(
+    roulette_start_depth: u32,
+    r: Ray,
+    world: &dyn Hittable,
+    camera: &Camera,
+    sampler: &mut dyn Sampler,
+    colors: &mut C,
+) -> C::Color {
+    let mut radiance = colors.constant(0.0);
+    let mut throughput = colors.constant(1.0);
+    let mut ray = r;
+    // Camera rays and specular bounces can't be sampled by the lights, so they see emission in full.
+    let mut specular_bounce = true;
+    let mut bsdf_pdf = 0.0;
+
+    for depth in 0..camera.max_depth {
+        let mut rec = HitRecord::default();
+        if !world.hit(&ray, 0.001, f64::INFINITY, &mut rec) {
+            radiance = radiance + throughput * colors.illuminant(camera.background.value(&ray));
+            break;
+        }
+
+        let Some(mat) = rec.mat.clone() else { break };
+
+        // Emission found by BSDF sampling, weighted against the chance of light sampling it.
+        let emitted = mat.emitted(rec.u, rec.v, &rec.p);
+        if !emitted.near_zero() {
+            let weight = match &camera.lights {
+                Some(lights) if !specular_bounce => power_heuristic(bsdf_pdf, lights.pdf_value(ray.orig(), ray.dir())),
+                _ => 1.0,
+            };
+            radiance = radiance + throughput * weight * colors.illuminant(emitted);
+        }
+
+        let mut srec = ScatterRecord { hero_wavelength: colors.hero_wavelength(), ..ScatterRecord::default() };
+        if !mat.scatter(&ray, &rec, &mut srec, sampler) {
+            break;
+        }
+        if srec.terminate_secondary_wavelengths {
+            colors.terminate_secondary();
+        }
+
+        if srec.skip_pdf {
+            throughput = throughput * colors.reflectance(srec.attenuation);
+            ray = srec.skip_pdf_ray.with_key(r.key());
+            specular_bounce = true;
+        } else {
+            let Some(surface_pdf) = srec.pdf else { break };
+
+            // Next event estimation: sample a direction toward the lights.
+            if let Some(lights) = &camera.lights {
+                let light_ray = Ray::new(rec.p, lights.random(rec.p, sampler), ray.time()).with_key(r.key());
+                let light_pdf = lights.pdf_value(rec.p, light_ray.dir());
+                if light_pdf > 0.0 {
+                    let f = mat.eval(&ray, &rec, &light_ray);
+                    let mut light_rec = HitRecord::default();
+                    if !f.near_zero() && world.hit(&light_ray, 0.001, f64::INFINITY, &mut light_rec) {
+                        if let Some(light_mat) = &light_rec.mat {
+                            let light_emitted = light_mat.emitted(light_rec.u, light_rec.v, &light_rec.p);
+                            let weight = power_heuristic(light_pdf, surface_pdf.value(light_ray.dir()));
+                            radiance = radiance
+                                + throughput * (weight / light_pdf) * colors.reflectance(f) * colors.illuminant(light_emitted);
+                        }
+                    }
+                }
+            }
+
+            // BSDF sampling picks the direction the path continues in.
+            let scattered = Ray::new(rec.p, surface_pdf.generate(sampler), ray.time()).with_key(r.key());
+            bsdf_pdf = surface_pdf.value(scattered.dir());
+            if bsdf_pdf <= 0.0 {
+                break;
+            }
+            throughput = throughput * colors.reflectance(mat.eval(&ray, &rec, &scattered)) / bsdf_pdf;
+            ray = scattered;
+            specular_bounce = false;
+        }
+
+        // Russian roulette: continue with probability proportional to the path's throughput,
+        // boosting survivors to keep the estimate unbiased.
+        if depth >= roulette_start_depth {
+            let survival = C::max_value(throughput).min(0.95);
+            if sampler.get_1d() >= survival {
+                break;
+            }
+            throughput = throughput / survival;
+        }
+    }
+
+    radiance
+}
+
+fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b > 0.0 { a / (a + b) } else { 0.0 }
+}
+
+// Shading normal at the first hit, mapped from [-1, 1] to [0, 1] per axis.
+pub struct NormalIntegrator;
+
+impl Integrator for NormalIntegrator {
+    fn ray_color(&self, r: Ray, world: &dyn Hittable, _camera: &Camera, _sampler: &mut dyn Sampler) -> Vector3 {
+        let mut rec = HitRecord::default();
+        if !world.hit(&r, 0.001, f64::INFINITY, &mut rec) {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+        0.5 * (rec.normal + Vector3::new(1.0, 1.0, 1.0))
+    }
+}
+
+// Surface (u, v) coordinates at the first hit, in the red and green channels.
+pub struct UvIntegrator;
+
+impl Integrator for UvIntegrator {
+    fn ray_color(&self, r: Ray, world: &dyn Hittable, _camera: &Camera, _sampler: &mut dyn Sampler) -> Vector3 {
+        let mut rec = HitRecord::default();
+        if !world.hit(&r, 0.001, f64::INFINITY, &mut rec) {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+        Vector3::new(rec.u, rec.v, 0.0)
+    }
+}
+
+// Distance to the first hit, from black up to white at max_distance. Misses are white.
+pub struct DepthIntegrator {
+    pub max_distance: f64,
+}
+
+impl DepthIntegrator {
+    pub fn new(max_distance: f64) -> Self { DepthIntegrator { max_distance } }
+}
+
+impl Integrator for DepthIntegrator {
+    fn ray_color(&self, r: Ray, world: &dyn Hittable, _camera: &Camera, _sampler: &mut dyn Sampler) -> Vector3 {
+        let mut rec = HitRecord::default();
+        if !world.hit(&r, 0.001, f64::INFINITY, &mut rec) {
+            return Vector3::new(1.0, 1.0, 1.0);
+        }
+        let depth = (rec.t * r.dir().length() / self.max_distance).min(1.0);
+        Vector3::new(depth, depth, depth)
+    }
+}
+
+// Material color at the first hit: the scattering attenuation, or the emission of lights.
+pub struct AlbedoIntegrator;
+
+impl Integrator for AlbedoIntegrator {
+    fn ray_color(&self, r: Ray, world: &dyn Hittable, _camera: &Camera, sampler: &mut dyn Sampler) -> Vector3 {
+        let mut rec = HitRecord::default();
+        if !world.hit(&r, 0.001, f64::INFINITY, &mut rec) {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+        let Some(mat) = &rec.mat else {
+            return Vector3::new(0.0, 0.0, 0.0);
+        };
+        let mut srec = ScatterRecord::default();
+        if mat.scatter(&r, &rec, &mut srec, sampler) {
+            srec.attenuation
+        } else {
+            mat.emitted(rec.u, rec.v, &rec.p)
+        }
+    }
+}
+
+// Fraction of cosine-weighted directions above the first hit that escape without hitting
+// anything within distance.
+pub struct AmbientOcclusionIntegrator {
+    pub samples: u32,
+    pub distance: f64,
+}
+
+impl AmbientOcclusionIntegrator {
+    pub fn new(samples: u32, distance: f64) -> Self { AmbientOcclusionIntegrator { samples, distance } }
+}
+
+impl Integrator for AmbientOcclusionIntegrator {
+    fn ray_color(&self, r: Ray, world: &dyn Hittable, _camera: &Camera, sampler: &mut dyn Sampler) -> Vector3 {
+        let mut rec = HitRecord::default();
+        if !world.hit(&r, 0.001, f64::INFINITY, &mut rec) {
+            return Vector3::new(1.0, 1.0, 1.0);
+        }
+
+        let uvw = Onb::new(rec.normal);
+        let samples = self.samples.max(1);
+        let mut unoccluded = 0;
+        for _ in 0..samples {
+            let direction = uvw.transform(sample_cosine_hemisphere(sampler.get_2d()));
+            let mut occluder = HitRecord::default();
+            if !world.hit(&Ray::new(rec.p, direction, r.time()).with_key(r.key()), 0.001, self.distance, &mut occluder) {
+                unoccluded += 1;
+            }
+        }
+
+        let visibility = unoccluded as f64 / samples as f64;
+        Vector3::new(visibility, visibility, visibility)
+    }
+}
@@ -1,17 +1,33 @@
 pub mod vector3;
 pub mod ray;
 pub mod hittable;
+pub mod aabb;
+pub mod bvh;
 pub mod sphere;
 pub mod moving_sphere;
+pub mod quad;
+pub mod triangle;
+pub mod obj;
+pub mod transform;
+pub mod constant_medium;
 pub mod material;
+pub mod microfacet;
+pub mod principled;
 pub mod camera;
+pub mod framebuffer;
+pub mod output;
+pub mod tonemap;
+pub mod progress;
+pub mod integrator;
+pub mod background;
 pub mod texture;
 pub mod scenes;
+pub mod scene_file;
 pub mod perlin;
-
-use rand::Rng;
-
-pub fn random_double(min: f64, max: f64) -> f64 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(min..max)
-}
\ No newline at end of file
+pub mod rng;
+pub mod sampler;
+pub mod spectrum;
+pub mod onb;
+pub mod pdf;
+#[cfg(test)]
+mod test_util;
@@ -1,7 +1,172 @@
-use ray_tracer::scenes;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+
+use clap::Parser;
+
+use ray_tracer::camera::RenderOverrides;
+use ray_tracer::integrator::SpectralIntegrator;
+use ray_tracer::output::{self, ExrPrecision};
+use ray_tracer::sampler::{sampler_by_name, Sampler};
+use ray_tracer::scene_file;
+use ray_tracer::scenes::{self, Scene};
+use ray_tracer::tonemap::{ToneMap, TransferFunction};
+use ray_tracer::progress::ConsoleProgress;
+
+#[derive(Parser)]
+#[command(about = "Renders one of the built-in scenes to an image file.")]
+struct Args {
+    /// Scene to render, see --list.
+    #[arg(default_value = "perlin")]
+    scene: Scene,
+
+    /// Render a JSON, TOML or YAML scene file instead of a built-in scene.
+    #[arg(short, long, conflicts_with = "scene")]
+    file: Option<PathBuf>,
+
+    /// Print the available scenes and exit.
+    #[arg(long)]
+    list: bool,
+
+    /// Image width in pixels; the height follows from the scene's aspect ratio.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    width: Option<u32>,
+
+    /// Samples per pixel.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    spp: Option<u32>,
+
+    /// Stop sampling a pixel once its estimated relative error is below this, e.g. 0.01.
+    /// --spp is then the maximum number of samples.
+    #[arg(long)]
+    noise_threshold: Option<f64>,
+
+    /// Minimum samples per pixel when sampling adaptively.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    min_spp: Option<u32>,
+
+    /// Sample generator: independent (the default), stratified, halton or sobol.
+    #[arg(long, value_parser = sampler_by_name)]
+    sampler: Option<Arc<dyn Sampler>>,
+
+    /// Render spectrally, with the spectral path tracer, so that dispersive glass splits light.
+    #[arg(long)]
+    spectral: bool,
+
+    /// Maximum number of bounces per path.
+    #[arg(long)]
+    max_depth: Option<u32>,
+
+    /// Seed for building the scene and sampling the image [default: the scene file's, or 0].
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Number of render threads, 0 for all cores.
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Output file [default: images/<scene or file name>.png]. The extension picks the format.
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Output format (png, jpg, exr, hdr or pfm), replacing the output file's extension.
+    #[arg(long)]
+    format: Option<String>,
+
+    /// Store OpenEXR output as 16-bit half floats instead of 32-bit floats.
+    #[arg(long)]
+    half: bool,
+
+    /// Exposure in stops for 8-bit output; each step doubles the brightness.
+    #[arg(long, allow_negative_numbers = true)]
+    exposure: Option<f64>,
+
+    /// Tone map for 8-bit output: clamp, reinhard, extended-reinhard, aces or uncharted2. The
+    /// last two take an optional white point, e.g. extended-reinhard:4.
+    #[arg(long)]
+    tonemap: Option<ToneMap>,
+
+    /// Encode 8-bit output with a pure power curve of this gamma instead of the sRGB curve.
+    #[arg(long, value_parser = positive)]
+    gamma: Option<f64>,
+
+    /// Dither 8-bit output to hide banding in gradients.
+    #[arg(long)]
+    dither: bool,
+
+    /// Also save an image of the number of samples taken per pixel.
+    #[arg(long)]
+    heatmap: Option<PathBuf>,
+}
+
+fn positive(text: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(value) if value > 0.0 => Ok(value),
+        _ => Err(format!("expected a positive number, got \"{}\"", text)),
+    }
+}
 
 fn main() {
-    let scene = scenes::Scene::PerlinScene;
-    let (world, camera) = scenes::get_scene(scene);
-    camera.render(&world, "perlin");
-}
\ No newline at end of file
+    let args = Args::parse();
+
+    if args.list {
+        for scene in Scene::ALL {
+            println!("{}", scene);
+        }
+        return;
+    }
+
+    let (world, mut camera) = match &args.file {
+        Some(path) => scene_file::load_scene(path, args.seed).unwrap_or_else(|error| {
+            eprintln!("Unable to load scene: {}", error);
+            process::exit(1);
+        }),
+        None => scenes::get_scene(args.scene, args.seed.unwrap_or_default()),
+    };
+    camera.apply_overrides(&RenderOverrides {
+        image_width: args.width,
+        samples_per_pixel: args.spp,
+        max_depth: args.max_depth,
+        seed: args.seed,
+        thread_count: args.threads,
+        noise_threshold: args.noise_threshold,
+        min_samples_per_pixel: args.min_spp,
+        exposure: args.exposure,
+        tone_map: args.tonemap,
+        transfer: args.gamma.map(TransferFunction::Gamma),
+        dither: args.dither.then_some(true),
+    });
+    if let Some(sampler) = args.sampler.clone() {
+        camera.sampler = sampler;
+    }
+    if args.spectral {
+        camera.integrator = Arc::new(SpectralIntegrator::default());
+    }
+    camera.progress = Arc::new(ConsoleProgress::new());
+
+    let name = match &args.file {
+        Some(path) => path.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+        None => args.scene.to_string(),
+    };
+    let mut output = args.output.unwrap_or_else(|| PathBuf::from(format!("images/{}.png", name)));
+    if let Some(format) = &args.format {
+        output.set_extension(format);
+    }
+
+    if let Err(error) = output::check_format(&output) {
+        eprintln!("Unable to save image: {}", error);
+        process::exit(1);
+    }
+
+    let exr_precision = if args.half { ExrPrecision::Half } else { ExrPrecision::Float };
+    let buffer = camera.render_to_file(&world, &output, exr_precision).unwrap_or_else(|error| {
+        eprintln!("Unable to save image: {}", error);
+        process::exit(1);
+    });
+    if let Some(heatmap) = &args.heatmap {
+        if let Err(error) = buffer.sample_heatmap().save(heatmap) {
+            eprintln!("Unable to save heatmap: {}", error);
+            process::exit(1);
+        }
+    }
+}
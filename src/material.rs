@@ -1,12 +1,57 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
-use crate::random_double;
 use crate::vector3::Vector3;
 use crate::ray::Ray;
 use crate::hittable::HitRecord;
 use crate::texture::{Texture, SolidColor};
+use crate::pdf::{CosinePdf, Pdf, SpherePdf};
+use crate::sampler::Sampler;
+use crate::onb::Onb;
+use crate::microfacet::{
+    fresnel_complex_rgb, fresnel_dielectric, fresnel_schlick, generalized_half_vector, refract, MicrofacetDielectricPdf,
+    MicrofacetReflectionPdf, TrowbridgeReitz,
+};
 
-pub trait Material {
-    fn scatter(&self, r: &Ray, rec: &HitRecord, attenuation: &mut Vector3, scattered: &mut Ray) -> bool;
+// Result of scattering at a surface. Specular lobes give their outgoing ray directly in
+// skip_pdf_ray, weighted by attenuation; other lobes give a pdf to sample directions from,
+// and the renderer weights each sampled direction by Material::eval.
+#[derive(Clone)]
+pub struct ScatterRecord {
+    pub attenuation: Vector3,
+    pub pdf: Option<Arc<dyn Pdf>>,
+    pub skip_pdf: bool,
+    pub skip_pdf_ray: Ray,
+    // Set by the spectral integrator before scattering, for materials whose scattering depends
+    // on wavelength. None for RGB paths.
+    pub hero_wavelength: Option<f64>,
+    // Set when the scattering depends on wavelength, see SampledWavelengths::terminate_secondary.
+    pub terminate_secondary_wavelengths: bool,
+}
+
+impl Default for ScatterRecord {
+    fn default() -> Self {
+        ScatterRecord {
+            attenuation: Vector3::new(0.0, 0.0, 0.0),
+            pdf: None,
+            skip_pdf: false,
+            skip_pdf_ray: Ray::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0), 0.0),
+            hero_wavelength: None,
+            terminate_secondary_wavelengths: false,
+        }
+    }
+}
+
+pub trait Material: Send + Sync {
+    fn scatter(&self, _r_in: &Ray, _rec: &HitRecord, _srec: &mut ScatterRecord, _sampler: &mut dyn Sampler) -> bool {
+        false
+    }
+    // BSDF times the cosine of the angle to the normal, for scattering r_in into scattered.
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _scattered: &Ray) -> Vector3 {
+        Vector3::new(0.0, 0.0, 0.0)
+    }
+    fn emitted(&self, _u: f64, _v: f64, _p: &Vector3) -> Vector3 {
+        Vector3::new(0.0, 0.0, 0.0)
+    }
 }
 
 pub struct Lambertian {
@@ -24,69 +69,359 @@ impl Lambertian {
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, r: &Ray, rec: &HitRecord, attenuation: &mut Vector3, scattered: &mut Ray) -> bool {
-        let mut scatter_direction = rec.normal + Vector3::random_unit_vector();
-        if scatter_direction.near_zero() {
-            scatter_direction = rec.normal;
-        }
-        *scattered = Ray::new(rec.p, scatter_direction, r.time());
-        *attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord, _sampler: &mut dyn Sampler) -> bool {
+        srec.attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
+        srec.pdf = Some(Arc::new(CosinePdf::new(rec.normal)));
+        srec.skip_pdf = false;
         true
     }
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Vector3 {
+        let cos_theta = rec.normal.dot(scattered.dir().unit_vector());
+        self.albedo.value(rec.u, rec.v, &rec.p) * (cos_theta.max(0.0) / PI)
+    }
 }
 
+// Metal with a tinted reflectance, for when measured optical constants aren't needed. The
+// fuzz is the roughness of a GGX lobe, and the albedo the reflectance at normal incidence.
 pub struct Metal {
     albedo: Vector3,
-    fuzz: f64
+    distribution: TrowbridgeReitz
 }
 
 impl Metal {
-    pub fn new(albedo: Vector3, fuzz: f64) -> Self { Metal { albedo, fuzz: if fuzz < 1.0 {fuzz} else {1.0} } }
+    pub fn new(albedo: Vector3, fuzz: f64) -> Self {
+        Metal { albedo, distribution: TrowbridgeReitz::from_roughness(fuzz) }
+    }
 }
 
 impl Material for Metal {
-    fn scatter(&self, r: &Ray, rec: &HitRecord, attenuation: &mut Vector3, scattered: &mut Ray) -> bool {
-        let mut reflected = r.dir().reflect(rec.normal);
-        reflected = reflected.unit_vector() + (self.fuzz * Vector3::random_unit_vector());
-        *scattered = Ray::new(rec.p, reflected, r.time());
-        *attenuation = self.albedo;
-        scattered.dir().dot(rec.normal) > 0.0
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord, _sampler: &mut dyn Sampler) -> bool {
+        scatter_microfacet(r_in, rec, srec, self.distribution, |cos_theta| fresnel_schlick(cos_theta, self.albedo))
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Vector3 {
+        eval_microfacet(r_in, rec, scattered, self.distribution, |cos_theta| fresnel_schlick(cos_theta, self.albedo))
+    }
+}
+
+// Physically based metal: a GGX microfacet lobe with the Fresnel reflectance of a complex
+// index of refraction eta + ik, given per RGB channel.
+pub struct Conductor {
+    eta: Vector3,
+    k: Vector3,
+    distribution: TrowbridgeReitz
+}
+
+impl Conductor {
+    pub fn new(eta: Vector3, k: Vector3, roughness: f64) -> Self {
+        Conductor { eta, k, distribution: TrowbridgeReitz::from_roughness(roughness) }
+    }
+
+    // Optical constants sampled at roughly 650, 550 and 450 nm.
+    pub fn gold(roughness: f64) -> Self {
+        Conductor::new(Vector3::new(0.143, 0.374, 1.442), Vector3::new(3.983, 2.385, 1.603), roughness)
+    }
+    pub fn silver(roughness: f64) -> Self {
+        Conductor::new(Vector3::new(0.155, 0.117, 0.138), Vector3::new(4.828, 3.122, 2.147), roughness)
+    }
+    pub fn copper(roughness: f64) -> Self {
+        Conductor::new(Vector3::new(0.200, 0.924, 1.102), Vector3::new(3.912, 2.452, 2.142), roughness)
+    }
+    pub fn aluminium(roughness: f64) -> Self {
+        Conductor::new(Vector3::new(1.657, 0.880, 0.521), Vector3::new(9.224, 6.270, 4.837), roughness)
     }
+
+    pub fn by_name(name: &str, roughness: f64) -> Option<Self> {
+        match name {
+            "gold" => Some(Conductor::gold(roughness)),
+            "silver" => Some(Conductor::silver(roughness)),
+            "copper" => Some(Conductor::copper(roughness)),
+            "aluminium" | "aluminum" => Some(Conductor::aluminium(roughness)),
+            _ => None,
+        }
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord, _sampler: &mut dyn Sampler) -> bool {
+        scatter_microfacet(r_in, rec, srec, self.distribution, |cos_theta| fresnel_complex_rgb(cos_theta, self.eta, self.k))
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Vector3 {
+        eval_microfacet(r_in, rec, scattered, self.distribution, |cos_theta| fresnel_complex_rgb(cos_theta, self.eta, self.k))
+    }
+}
+
+// Scatters off a GGX reflection lobe with the given Fresnel term. Lobes too narrow to sample
+// are treated as a perfect mirror.
+fn scatter_microfacet(
+    r_in: &Ray,
+    rec: &HitRecord,
+    srec: &mut ScatterRecord,
+    distribution: TrowbridgeReitz,
+    fresnel: impl Fn(f64) -> Vector3,
+) -> bool {
+    let wo = -r_in.dir().unit_vector();
+    srec.attenuation = fresnel(wo.dot(rec.normal));
+    if distribution.effectively_smooth() {
+        srec.pdf = None;
+        srec.skip_pdf = true;
+        srec.skip_pdf_ray = Ray::new(rec.p, r_in.dir().unit_vector().reflect(rec.normal), r_in.time());
+        return true;
+    }
+    srec.pdf = Some(Arc::new(MicrofacetReflectionPdf::new(rec.normal, wo, distribution)));
+    srec.skip_pdf = false;
+    true
 }
 
+// Torrance-Sparrow reflection, D * G * F / (4 cos(theta_o) cos(theta_i)), times cos(theta_i).
+fn eval_microfacet(
+    r_in: &Ray,
+    rec: &HitRecord,
+    scattered: &Ray,
+    distribution: TrowbridgeReitz,
+    fresnel: impl Fn(f64) -> Vector3,
+) -> Vector3 {
+    let uvw = Onb::new(rec.normal);
+    let wo = uvw.to_local(-r_in.dir().unit_vector());
+    let wi = uvw.to_local(scattered.dir().unit_vector());
+    let wm = wo + wi;
+    if wo.z() <= 0.0 || wi.z() <= 0.0 || wm.length_squared() == 0.0 {
+        return Vector3::new(0.0, 0.0, 0.0);
+    }
+    let wm = wm.unit_vector();
+    fresnel(wo.dot(wm)) * (distribution.d(wm) * distribution.g(wo, wi) / (4.0 * wo.z()))
+}
+
+// Smooth glass. Light travelling through the inside is absorbed following the Beer-Lambert law,
+// which assumes the object is closed so every exit hit is preceded by an entry.
 pub struct Dielectric {
-    refraction_index: f64
+    refraction_index: f64,
+    // Absorption coefficient per unit length, per channel.
+    absorption: Vector3,
+    // Used instead of refraction_index for rays carrying wavelengths.
+    dispersion: Option<Dispersion>
 }
 
 impl Dielectric {
-    pub fn new(refraction_index: f64) -> Self { Dielectric { refraction_index } }
+    pub fn new(refraction_index: f64) -> Self { Dielectric::new_with_absorption(refraction_index, Vector3::new(0.0, 0.0, 0.0)) }
+    pub fn new_with_absorption(refraction_index: f64, absorption: Vector3) -> Self {
+        Dielectric { refraction_index, absorption, dispersion: None }
+    }
+    // Glass splitting light into its colors when rendering spectrally. RGB renders use the index
+    // at the sodium d line.
+    pub fn new_dispersive(dispersion: Dispersion, absorption: Vector3) -> Self {
+        Dielectric { refraction_index: dispersion.refraction_index(587.6), absorption, dispersion: Some(dispersion) }
+    }
+    // Colored glass letting through the given fraction of each channel after travelling the
+    // given distance inside it.
+    pub fn tinted(refraction_index: f64, transmittance: Vector3, distance: f64) -> Self {
+        Dielectric::new_with_absorption(refraction_index, Dielectric::absorption_from_transmittance(transmittance, distance))
+    }
+    pub fn absorption_from_transmittance(transmittance: Vector3, distance: f64) -> Vector3 {
+        let absorption = |t: f64| -t.clamp(1e-6, 1.0).ln() / distance;
+        Vector3::new(absorption(transmittance.x()), absorption(transmittance.y()), absorption(transmittance.z()))
+    }
 
-    fn reflectance(&self, cosine: f64) -> f64 {
+    fn reflectance(cosine: f64, refraction_index: f64) -> f64 {
         // Use Schlick's approximation for reflectance.
-        let mut r0 = (1.0 - self.refraction_index) / (1.0 + self.refraction_index);
+        let mut r0 = (1.0 - refraction_index) / (1.0 + refraction_index);
         r0 = r0 * r0;
         r0 + (1.0 - r0) * (1.0 - cosine).powf(5.0)
     }
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, r: &Ray, rec: &HitRecord, attenuation: &mut Vector3, scattered: &mut Ray) -> bool {
-        *attenuation = Vector3::new(1.0, 1.0, 1.0);
-        let ri = if rec.front_face { 1.0 / self.refraction_index } else { self.refraction_index };
-        let unit_direction = r.dir().unit_vector();
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord, sampler: &mut dyn Sampler) -> bool {
+        // Hitting the inside of the surface means the ray has just crossed the medium.
+        srec.attenuation = if rec.front_face {
+            Vector3::new(1.0, 1.0, 1.0)
+        } else {
+            let distance = rec.t * r_in.dir().length();
+            Vector3::new(
+                (-self.absorption.x() * distance).exp(),
+                (-self.absorption.y() * distance).exp(),
+                (-self.absorption.z() * distance).exp(),
+            )
+        };
+        // Dispersion sends each wavelength its own way, so the path continues with just the hero.
+        let refraction_index = match (self.dispersion, srec.hero_wavelength) {
+            (Some(dispersion), Some(lambda)) => {
+                srec.terminate_secondary_wavelengths = true;
+                dispersion.refraction_index(lambda)
+            }
+            _ => self.refraction_index,
+        };
+        srec.pdf = None;
+        srec.skip_pdf = true;
+        let ri = if rec.front_face { 1.0 / refraction_index } else { refraction_index };
+        let unit_direction = r_in.dir().unit_vector();
 
-        let cos_theta = f64::min(-1.0 * unit_direction.dot(rec.normal), 1.0);
+        let cos_theta = f64::min(-unit_direction.dot(rec.normal), 1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = ri * sin_theta > 1.0;
-        let direction;
-        if cannot_refract || self.reflectance(cos_theta) > random_double(0.0, 1.0) {
-            direction = unit_direction.reflect(rec.normal);
+        let direction = if cannot_refract || Dielectric::reflectance(cos_theta, refraction_index) > sampler.get_1d() {
+            unit_direction.reflect(rec.normal)
         } else {
-            direction = unit_direction.refract(rec.normal, ri);
+            // Radiance is compressed into a smaller solid angle going into the denser side, as
+            // with RoughDielectric. The factors cancel for paths that enter and leave an object.
+            srec.attenuation = srec.attenuation * (ri * ri);
+            unit_direction.refract(rec.normal, ri)
+        };
+
+        srec.skip_pdf_ray = Ray::new(rec.p, direction, r_in.time());
+        true
+    }
+}
+
+// Wavelength dependent index of refraction, with wavelengths in micrometers in the formulas.
+#[derive(Clone, Copy, Debug)]
+pub enum Dispersion {
+    // n = a + b / lambda^2
+    Cauchy { a: f64, b: f64 },
+    // n^2 = 1 + sum over i of b_i lambda^2 / (lambda^2 - c_i)
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    pub const BK7: Dispersion =
+        Dispersion::Sellmeier { b: [1.03961212, 0.231792344, 1.01046945], c: [0.00600069867, 0.0200179144, 103.560653] };
+    pub const FUSED_SILICA: Dispersion =
+        Dispersion::Sellmeier { b: [0.6961663, 0.4079426, 0.8974794], c: [0.00467914826, 0.0135120631, 97.9340025] };
+    pub const DIAMOND: Dispersion = Dispersion::Sellmeier { b: [0.3306, 4.3356, 0.0], c: [0.030625, 0.011236, 0.0] };
+
+    pub fn by_name(name: &str) -> Option<Dispersion> {
+        match name {
+            "bk7" => Some(Dispersion::BK7),
+            "fused_silica" => Some(Dispersion::FUSED_SILICA),
+            "diamond" => Some(Dispersion::DIAMOND),
+            _ => None,
+        }
+    }
+
+    pub fn refraction_index(&self, lambda_nm: f64) -> f64 {
+        let lambda = lambda_nm / 1000.0;
+        let lambda2 = lambda * lambda;
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / lambda2,
+            Dispersion::Sellmeier { b, c } => {
+                (1.0 + (0..3).map(|i| b[i] * lambda2 / (lambda2 - c[i])).sum::<f64>()).sqrt()
+            }
         }
+    }
+}
+
+// Frosted glass: GGX reflection and transmission lobes weighted by the exact Fresnel equations.
+// The roughness may vary over the surface, read from a grayscale texture.
+pub struct RoughDielectric {
+    refraction_index: f64,
+    roughness: Arc<dyn Texture>
+}
+
+impl RoughDielectric {
+    pub fn new(refraction_index: f64, roughness: f64) -> Self {
+        RoughDielectric { refraction_index, roughness: Arc::new(SolidColor::from_rgb(roughness, roughness, roughness)) }
+    }
+    pub fn new_from_texture(refraction_index: f64, roughness: Arc<dyn Texture>) -> Self {
+        RoughDielectric { refraction_index, roughness }
+    }
+
+    fn distribution(&self, rec: &HitRecord) -> TrowbridgeReitz {
+        TrowbridgeReitz::from_roughness(self.roughness.scalar_value(rec.u, rec.v, &rec.p))
+    }
+    // Index of refraction on the far side of the surface relative to the side the ray came from.
+    fn eta(&self, rec: &HitRecord) -> f64 {
+        if rec.front_face { self.refraction_index } else { 1.0 / self.refraction_index }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord, sampler: &mut dyn Sampler) -> bool {
+        let distribution = self.distribution(rec);
+        let eta = self.eta(rec);
+        let wo = -r_in.dir().unit_vector();
+        srec.attenuation = Vector3::new(1.0, 1.0, 1.0);
+        if !distribution.effectively_smooth() {
+            srec.pdf = Some(Arc::new(MicrofacetDielectricPdf::new(rec.normal, wo, distribution, eta)));
+            srec.skip_pdf = false;
+            return true;
+        }
+
+        srec.pdf = None;
+        srec.skip_pdf = true;
+        let r = fresnel_dielectric(wo.dot(rec.normal), eta);
+        let direction = match refract(wo, rec.normal, eta) {
+            Some(refracted) if sampler.get_1d() >= r => {
+                // Radiance is compressed into a smaller solid angle going into the denser side.
+                srec.attenuation = srec.attenuation / (eta * eta);
+                refracted
+            }
+            _ => (-wo).reflect(rec.normal),
+        };
+        srec.skip_pdf_ray = Ray::new(rec.p, direction, r_in.time());
+        true
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Vector3 {
+        let distribution = self.distribution(rec);
+        let eta = self.eta(rec);
+        let uvw = Onb::new(rec.normal);
+        let wo = uvw.to_local(-r_in.dir().unit_vector());
+        let wi = uvw.to_local(scattered.dir().unit_vector());
+        let Some(wm) = generalized_half_vector(wo, wi, eta) else {
+            return Vector3::new(0.0, 0.0, 0.0);
+        };
+
+        let r = fresnel_dielectric(wo.dot(wm), eta);
+        let f = if wo.z() * wi.z() > 0.0 {
+            distribution.d(wm) * distribution.g(wo, wi) * r / (4.0 * wo.z().abs())
+        } else {
+            let denom = wi.dot(wm) + wo.dot(wm) / eta;
+            distribution.d(wm) * distribution.g(wo, wi) * (1.0 - r) * (wi.dot(wm) * wo.dot(wm) / (wo.z() * denom * denom)).abs()
+                / (eta * eta)
+        };
+        Vector3::new(f, f, f)
+    }
+}
+
+pub struct DiffuseLight {
+    emit: Arc<dyn Texture>
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Vector3) -> Self {
+        DiffuseLight { emit: Arc::new(SolidColor::new(emit)) }
+    }
+    pub fn new_from_texture(emit: Arc<dyn Texture>) -> Self {
+        DiffuseLight { emit }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn emitted(&self, u: f64, v: f64, p: &Vector3) -> Vector3 {
+        self.emit.value(u, v, p)
+    }
+}
 
-        *scattered = Ray::new(rec.p, direction, r.time());
+pub struct Isotropic {
+    albedo: Arc<dyn Texture>
+}
+
+impl Isotropic {
+    pub fn new(albedo: Vector3) -> Self {
+        Isotropic { albedo: Arc::new(SolidColor::new(albedo)) }
+    }
+    pub fn new_from_texture(albedo: Arc<dyn Texture>) -> Self {
+        Isotropic { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, _r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord, _sampler: &mut dyn Sampler) -> bool {
+        srec.attenuation = self.albedo.value(rec.u, rec.v, &rec.p);
+        srec.pdf = Some(Arc::new(SpherePdf));
+        srec.skip_pdf = false;
         true
     }
-}
\ No newline at end of file
+    fn eval(&self, _r_in: &Ray, rec: &HitRecord, _scattered: &Ray) -> Vector3 {
+        self.albedo.value(rec.u, rec.v, &rec.p) / (4.0 * PI)
+    }
+}
@@ -0,0 +1,380 @@
+use std::f64::consts::PI;
+use std::ops::{Add, Div, Mul, Sub};
+
+use crate::onb::Onb;
+use crate::pdf::Pdf;
+use crate::sampler::Sampler;
+use crate::vector3::Vector3;
+
+// Trowbridge-Reitz (GGX) distribution of microfacet normals with Smith masking-shadowing.
+// Directions are in the local shading frame, with the surface normal along z. Anisotropic
+// distributions are stretched by alpha_x along x and alpha_y along y.
+#[derive(Clone, Copy, Debug)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha: f64) -> Self { TrowbridgeReitz { alpha_x: alpha, alpha_y: alpha } }
+    pub fn anisotropic(alpha_x: f64, alpha_y: f64) -> Self { TrowbridgeReitz { alpha_x, alpha_y } }
+
+    // Perceptually linear roughness in [0, 1] is squared to get alpha.
+    pub fn from_roughness(roughness: f64) -> Self {
+        let roughness = roughness.clamp(0.0, 1.0);
+        TrowbridgeReitz::new(roughness * roughness)
+    }
+
+    pub fn alpha_x(&self) -> f64 { self.alpha_x }
+    pub fn alpha_y(&self) -> f64 { self.alpha_y }
+
+    // Below this the lobe is narrower than we can sample reliably, so treat it as a mirror.
+    pub fn effectively_smooth(&self) -> bool { self.alpha_x.max(self.alpha_y) < 1e-3 }
+
+    pub fn d(&self, wm: Vector3) -> f64 {
+        let cos2_theta = wm.z() * wm.z();
+        if cos2_theta <= 0.0 {
+            return 0.0;
+        }
+        let x = wm.x() / self.alpha_x;
+        let y = wm.y() / self.alpha_y;
+        let e = 1.0 + (x * x + y * y) / cos2_theta;
+        1.0 / (PI * self.alpha_x * self.alpha_y * cos2_theta * cos2_theta * e * e)
+    }
+
+    pub fn lambda(&self, w: Vector3) -> f64 {
+        let cos2_theta = w.z() * w.z();
+        if cos2_theta <= 0.0 {
+            return 0.0;
+        }
+        let x = self.alpha_x * w.x();
+        let y = self.alpha_y * w.y();
+        ((1.0 + (x * x + y * y) / cos2_theta).sqrt() - 1.0) / 2.0
+    }
+
+    pub fn g1(&self, w: Vector3) -> f64 { 1.0 / (1.0 + self.lambda(w)) }
+
+    // Height-correlated masking-shadowing for the pair of directions.
+    pub fn g(&self, wo: Vector3, wi: Vector3) -> f64 { 1.0 / (1.0 + self.lambda(wo) + self.lambda(wi)) }
+
+    // Density of the microfacet normals visible from w.
+    pub fn d_visible(&self, w: Vector3, wm: Vector3) -> f64 {
+        if w.z() == 0.0 {
+            return 0.0;
+        }
+        self.g1(w) / w.z().abs() * self.d(wm) * w.dot(wm).abs()
+    }
+
+    // Samples a visible microfacet normal for direction w (Heitz 2018): stretch w into the
+    // configuration where alpha is 1, sample the projected hemisphere there and unstretch.
+    pub fn sample_wm(&self, w: Vector3, u: (f64, f64)) -> Vector3 {
+        let w = if w.z() < 0.0 { -w } else { w };
+        let wh = Vector3::new(self.alpha_x * w.x(), self.alpha_y * w.y(), w.z()).unit_vector();
+        let t1 = if wh.z() < 0.99999 {
+            Vector3::new(0.0, 0.0, 1.0).cross(wh).unit_vector()
+        } else {
+            Vector3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = wh.cross(t1);
+
+        let r = u.0.sqrt();
+        let phi = 2.0 * PI * u.1;
+        let px = r * phi.cos();
+        let py = r * phi.sin();
+        let h = (1.0 - px * px).sqrt();
+        let s = (1.0 + wh.z()) / 2.0;
+        let py = (1.0 - s) * h + s * py;
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+
+        let nh = px * t1 + py * t2 + pz * wh;
+        Vector3::new(self.alpha_x * nh.x(), self.alpha_y * nh.y(), nh.z().max(1e-6)).unit_vector()
+    }
+}
+
+// Reflection off a GGX lobe, sampled through the visible normals seen from wo.
+pub struct MicrofacetReflectionPdf {
+    uvw: Onb,
+    wo: Vector3,
+    distribution: TrowbridgeReitz,
+}
+
+impl MicrofacetReflectionPdf {
+    // wo points away from the surface, in world space.
+    pub fn new(normal: Vector3, wo: Vector3, distribution: TrowbridgeReitz) -> Self {
+        let uvw = Onb::new(normal);
+        MicrofacetReflectionPdf { uvw, wo: uvw.to_local(wo.unit_vector()), distribution }
+    }
+}
+
+impl Pdf for MicrofacetReflectionPdf {
+    fn value(&self, direction: Vector3) -> f64 {
+        let wi = self.uvw.to_local(direction.unit_vector());
+        if wi.z() * self.wo.z() <= 0.0 {
+            return 0.0;
+        }
+        let wm = self.wo + wi;
+        if wm.length_squared() == 0.0 {
+            return 0.0;
+        }
+        let wm = wm.unit_vector();
+        let wm = if wm.z() < 0.0 { -wm } else { wm };
+        self.distribution.d_visible(self.wo, wm) / (4.0 * self.wo.dot(wm).abs())
+    }
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vector3 {
+        let wm = self.distribution.sample_wm(self.wo, sampler.get_2d());
+        self.uvw.transform((-self.wo).reflect(wm))
+    }
+}
+
+// Reflection and transmission through a GGX lobe on the boundary between two dielectrics.
+// eta is the index of refraction on the far side of the surface relative to the side wo is on.
+// Reflection is chosen with probability given by the Fresnel reflectance of the sampled normal.
+pub struct MicrofacetDielectricPdf {
+    uvw: Onb,
+    wo: Vector3,
+    distribution: TrowbridgeReitz,
+    eta: f64,
+}
+
+impl MicrofacetDielectricPdf {
+    // The normal must be on the same side of the surface as wo.
+    pub fn new(normal: Vector3, wo: Vector3, distribution: TrowbridgeReitz, eta: f64) -> Self {
+        MicrofacetDielectricPdf::with_frame(Onb::new(normal), wo, distribution, eta)
+    }
+    // For anisotropic distributions, in a shading frame oriented along the surface.
+    pub fn with_frame(uvw: Onb, wo: Vector3, distribution: TrowbridgeReitz, eta: f64) -> Self {
+        MicrofacetDielectricPdf { uvw, wo: uvw.to_local(wo.unit_vector()), distribution, eta }
+    }
+}
+
+impl Pdf for MicrofacetDielectricPdf {
+    // Sampled reflections can end up below the surface and refractions above it on rough
+    // surfaces, so both lobes contribute to the density on either side.
+    fn value(&self, direction: Vector3) -> f64 {
+        let wo = self.wo;
+        let wi = self.uvw.to_local(direction.unit_vector());
+        let mut density = 0.0;
+
+        let wm = wo + wi;
+        if wm.length_squared() > 0.0 {
+            let wm = wm.unit_vector();
+            if wm.z() > 0.0 {
+                let r = fresnel_dielectric(wo.dot(wm), self.eta);
+                density += self.distribution.d_visible(wo, wm) / (4.0 * wo.dot(wm)) * r;
+            }
+        }
+
+        let wm = self.eta * wi + wo;
+        if wm.length_squared() > 0.0 {
+            let wm = wm.unit_vector();
+            let wm = if wm.dot(wo) < 0.0 { -wm } else { wm };
+            if wm.z() > 0.0 && wi.dot(wm) < 0.0 {
+                let r = fresnel_dielectric(wo.dot(wm), self.eta);
+                let denom = wi.dot(wm) + wo.dot(wm) / self.eta;
+                density += self.distribution.d_visible(wo, wm) * wi.dot(wm).abs() / (denom * denom) * (1.0 - r);
+            }
+        }
+        density
+    }
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vector3 {
+        let wm = self.distribution.sample_wm(self.wo, sampler.get_2d());
+        let r = fresnel_dielectric(self.wo.dot(wm), self.eta);
+        let wi = if sampler.get_1d() < r {
+            (-self.wo).reflect(wm)
+        } else {
+            refract(self.wo, wm, self.eta).unwrap_or_else(|| (-self.wo).reflect(wm))
+        };
+        self.uvw.transform(wi)
+    }
+}
+
+// Microfacet normal that scatters wo into wi, by reflection when both are on the same side of
+// the surface and by refraction otherwise, facing the positive z side. None if there isn't one,
+// or if it faces away from either direction.
+pub fn generalized_half_vector(wo: Vector3, wi: Vector3, eta: f64) -> Option<Vector3> {
+    let cos_theta_o = wo.z();
+    let cos_theta_i = wi.z();
+    if cos_theta_o == 0.0 || cos_theta_i == 0.0 {
+        return None;
+    }
+    let eta_i = if cos_theta_o * cos_theta_i > 0.0 { 1.0 } else if cos_theta_o > 0.0 { eta } else { 1.0 / eta };
+    let wm = eta_i * wi + wo;
+    if wm.length_squared() == 0.0 {
+        return None;
+    }
+    let wm = wm.unit_vector();
+    let wm = if wm.z() < 0.0 { -wm } else { wm };
+    if wm.dot(wi) * cos_theta_i < 0.0 || wm.dot(wo) * cos_theta_o < 0.0 {
+        return None;
+    }
+    Some(wm)
+}
+
+// Refracts w, pointing away from the surface, through a surface with normal n and relative
+// index of refraction eta. None on total internal reflection.
+pub fn refract(w: Vector3, n: Vector3, eta: f64) -> Option<Vector3> {
+    let mut cos_theta_i = n.dot(w);
+    let (eta, n) = if cos_theta_i < 0.0 {
+        cos_theta_i = -cos_theta_i;
+        (1.0 / eta, -n)
+    } else {
+        (eta, n)
+    };
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i).max(0.0) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some(-w / eta + (cos_theta_i / eta - cos_theta_t) * n)
+}
+
+// Exact reflectance of a dielectric boundary for unpolarized light, with eta the relative index
+// of refraction on the far side. Negative cosines are incident from the far side.
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let mut cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+    let mut eta = eta;
+    if cos_theta_i < 0.0 {
+        eta = 1.0 / eta;
+        cos_theta_i = -cos_theta_i;
+    }
+    let sin2_theta_t = (1.0 - cos_theta_i * cos_theta_i) / (eta * eta);
+    if sin2_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = (1.0 - sin2_theta_t).max(0.0).sqrt();
+    let r_parl = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let r_perp = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    (r_parl * r_parl + r_perp * r_perp) / 2.0
+}
+
+// Reflectance of a conductor with complex index of refraction eta + ik, for unpolarized light.
+pub fn fresnel_complex(cos_theta_i: f64, eta: f64, k: f64) -> f64 {
+    let cos_theta_i = cos_theta_i.clamp(0.0, 1.0);
+    let eta = Complex::new(eta, k);
+    let sin2_theta_i = Complex::new(1.0 - cos_theta_i * cos_theta_i, 0.0);
+    let sin2_theta_t = sin2_theta_i / (eta * eta);
+    let cos_theta_t = (Complex::new(1.0, 0.0) - sin2_theta_t).sqrt();
+    let cos_i = Complex::new(cos_theta_i, 0.0);
+
+    let r_parl = (eta * cos_i - cos_theta_t) / (eta * cos_i + cos_theta_t);
+    let r_perp = (cos_i - eta * cos_theta_t) / (cos_i + eta * cos_theta_t);
+    (r_parl.norm() + r_perp.norm()) / 2.0
+}
+
+pub fn fresnel_complex_rgb(cos_theta_i: f64, eta: Vector3, k: Vector3) -> Vector3 {
+    Vector3::new(
+        fresnel_complex(cos_theta_i, eta.x(), k.x()),
+        fresnel_complex(cos_theta_i, eta.y(), k.y()),
+        fresnel_complex(cos_theta_i, eta.z(), k.z()),
+    )
+}
+
+// Schlick's approximation with the reflectance at normal incidence given per channel.
+pub fn fresnel_schlick(cos_theta_i: f64, f0: Vector3) -> Vector3 {
+    let m = (1.0 - cos_theta_i.clamp(0.0, 1.0)).powi(5);
+    f0 + m * (Vector3::new(1.0, 1.0, 1.0) - f0)
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self { Complex { re, im } }
+
+    // Squared magnitude.
+    fn norm(&self) -> f64 { self.re * self.re + self.im * self.im }
+
+    // Principal square root.
+    fn sqrt(&self) -> Complex {
+        let n = self.norm().sqrt();
+        if n == 0.0 {
+            return Complex::new(0.0, 0.0);
+        }
+        let t1 = (0.5 * (n + self.re.abs())).sqrt();
+        let t2 = 0.5 * self.im / t1;
+        if self.re >= 0.0 {
+            Complex::new(t1, t2)
+        } else {
+            Complex::new(t2.abs(), t1.copysign(self.im))
+        }
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, other: Complex) -> Complex { Complex::new(self.re + other.re, self.im + other.im) }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, other: Complex) -> Complex { Complex::new(self.re - other.re, self.im - other.im) }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(self.re * other.re - self.im * other.im, self.re * other.im + self.im * other.re)
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, other: Complex) -> Complex {
+        let scale = 1.0 / other.norm();
+        Complex::new(
+            scale * (self.re * other.re + self.im * other.im),
+            scale * (self.im * other.re - self.re * other.im),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Conductor, RoughDielectric};
+    use crate::test_util::{assert_close, assert_consistent_pdf, assert_eval_matches_sampling, average_over_samples, integrate_sphere};
+
+    #[test]
+    fn fresnel_complex_without_absorption_matches_dielectric() {
+        for eta in [1.33, 1.5, 2.4] {
+            for i in 0..=10 {
+                let cos_theta = i as f64 / 10.0;
+                assert_close(fresnel_complex(cos_theta, eta, 0.0), fresnel_dielectric(cos_theta, eta), 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn microfacet_reflection_pdf_is_consistent() {
+        let wo = Vector3::new(0.3, 0.0, 1.0).unit_vector();
+        let pdf = MicrofacetReflectionPdf::new(Vector3::new(0.0, 0.0, 1.0), wo, TrowbridgeReitz::new(0.2));
+        // Samples reflected below the surface get no density, so the pdf integrates to the
+        // fraction of samples that stay above it.
+        let above = average_over_samples(&pdf, |w| if w.z() > 0.0 { 1.0 } else { 0.0 });
+        assert_close(integrate_sphere(|w| pdf.value(w)), above, 0.01);
+        let expected = integrate_sphere(|w| pdf.value(w) * pdf.value(w));
+        assert_close(average_over_samples(&pdf, |w| pdf.value(w)), expected, 0.02 * expected);
+    }
+
+    #[test]
+    fn microfacet_dielectric_pdf_is_consistent() {
+        let normal = Vector3::new(0.0, 0.0, 1.0);
+        let wo = Vector3::new(0.5, 0.2, 1.0).unit_vector();
+        assert_consistent_pdf(&MicrofacetDielectricPdf::new(normal, wo, TrowbridgeReitz::new(0.3), 1.5));
+        // From inside, where total internal reflection sends some samples back.
+        assert_consistent_pdf(&MicrofacetDielectricPdf::new(normal, wo, TrowbridgeReitz::new(0.3), 1.0 / 1.5));
+    }
+
+    #[test]
+    fn conductor_eval_matches_sampling() {
+        assert_eval_matches_sampling(&Conductor::gold(0.5), Vector3::new(0.5, 0.0, 1.0).unit_vector());
+    }
+
+    #[test]
+    fn rough_dielectric_eval_matches_sampling() {
+        assert_eval_matches_sampling(&RoughDielectric::new(1.5, 0.6), Vector3::new(0.5, 0.0, 1.0).unit_vector());
+    }
+}
@@ -5,6 +5,8 @@ use crate::vector3::Vector3;
 use crate::ray::Ray;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::sphere::sphere_tangent;
 
 pub struct MovingSphere {
     center0: Vector3,
@@ -51,8 +53,17 @@ impl Hittable for MovingSphere {
         rec.p = r.at(rec.t);
         let outward_normal = (rec.p - self.center(r.time())) / self.radius();
         rec.set_face_normal(r, &outward_normal);
+        rec.tangent = sphere_tangent(outward_normal);
         rec.mat = self.mat.clone();
 
         true
     }
+
+    fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+        let rvec = Vector3::new(self.radius(), self.radius(), self.radius());
+        let box0 = Aabb::new(self.center(time0) - rvec, self.center(time0) + rvec);
+        let box1 = Aabb::new(self.center(time1) - rvec, self.center(time1) + rvec);
+        *output_box = Aabb::surrounding_box(&box0, &box1);
+        true
+    }
 }
\ No newline at end of file
@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::hittable::HittableList;
+use crate::material::{Dielectric, Lambertian, Material, Metal};
+use crate::texture::ImageTexture;
+use crate::triangle::{MeshBuffers, TriangleMesh, VertexIndex};
+use crate::vector3::Vector3;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io { path: PathBuf, source: io::Error },
+    Parse { path: PathBuf, line: usize, message: String },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            ObjError::Parse { path, line, message } => write!(f, "{}:{}: {}", path.display(), line, message),
+        }
+    }
+}
+
+impl Error for ObjError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ObjError::Io { source, .. } => Some(source),
+            ObjError::Parse { .. } => None,
+        }
+    }
+}
+
+// Loads a Wavefront OBJ file, and any MTL libraries it references, into one TriangleMesh per
+// material. The meshes share a single set of vertex buffers.
+pub fn load_obj(path: impl AsRef<Path>) -> Result<HittableList, ObjError> {
+    let path = path.as_ref();
+    let source = read_file(path)?;
+    let error = |line: usize, message: String| ObjError::Parse { path: path.to_path_buf(), line, message };
+
+    let mut buffers = MeshBuffers::default();
+    let mut materials: HashMap<String, Arc<dyn Material>> = HashMap::new();
+    let mut groups = vec![FaceGroup { material: None, faces: Vec::new() }];
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line = index + 1;
+        let tokens = tokenize(raw_line);
+        let Some((&keyword, args)) = tokens.split_first() else { continue };
+
+        match keyword {
+            "v" => {
+                let v = parse_floats(args, 3, 4).map_err(|m| error(line, m))?;
+                buffers.positions.push(Vector3::new(v[0], v[1], v[2]));
+            }
+            "vn" => {
+                let n = parse_floats(args, 3, 3).map_err(|m| error(line, m))?;
+                buffers.normals.push(Vector3::new(n[0], n[1], n[2]));
+            }
+            "vt" => {
+                let t = parse_floats(args, 1, 3).map_err(|m| error(line, m))?;
+                buffers.uvs.push((t[0], t.get(1).copied().unwrap_or(0.0)));
+            }
+            "f" => {
+                if args.len() < 3 {
+                    return Err(error(line, format!("face needs at least 3 vertices, found {}", args.len())));
+                }
+                let corners = args.iter()
+                    .map(|corner| parse_face_vertex(corner, &buffers))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|m| error(line, m))?;
+                // Triangulate polygons as a fan around the first corner.
+                let faces = &mut groups.last_mut().unwrap().faces;
+                for i in 1..corners.len() - 1 {
+                    faces.push([corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            "mtllib" => {
+                if args.is_empty() {
+                    return Err(error(line, "mtllib needs a file name".to_string()));
+                }
+                let directory = path.parent().unwrap_or_else(|| Path::new(""));
+                for library in args {
+                    materials.extend(load_mtl(directory.join(library))?);
+                }
+            }
+            "usemtl" => {
+                let name = args.first().ok_or_else(|| error(line, "usemtl needs a material name".to_string()))?;
+                groups.push(FaceGroup { material: Some((name.to_string(), line)), faces: Vec::new() });
+            }
+            // Grouping, smoothing groups, curves and other elements don't affect rendering.
+            _ => {}
+        }
+    }
+
+    let buffers = Arc::new(buffers);
+    let default_material: Arc<dyn Material> = Arc::new(Lambertian::new(Vector3::new(0.8, 0.8, 0.8)));
+    let mut meshes = HittableList::new();
+    for FaceGroup { material, faces } in groups {
+        if faces.is_empty() {
+            continue;
+        }
+        let mat = match material {
+            Some((name, line)) => materials.get(&name).cloned()
+                .ok_or_else(|| error(line, format!("material '{}' is not defined in any mtllib", name)))?,
+            None => default_material.clone(),
+        };
+        meshes.push(TriangleMesh::new(buffers.clone(), &faces, Some(mat)));
+    }
+    Ok(meshes)
+}
+
+// Loads the materials of an MTL library, keyed by name.
+pub fn load_mtl(path: impl AsRef<Path>) -> Result<HashMap<String, Arc<dyn Material>>, ObjError> {
+    let path = path.as_ref();
+    let source = read_file(path)?;
+    let error = |line: usize, message: String| ObjError::Parse { path: path.to_path_buf(), line, message };
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlDescription)> = None;
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line = index + 1;
+        let tokens = tokenize(raw_line);
+        let Some((&keyword, args)) = tokens.split_first() else { continue };
+
+        if keyword == "newmtl" {
+            let name = args.first().ok_or_else(|| error(line, "newmtl needs a material name".to_string()))?;
+            if let Some((name, description)) = current.take() {
+                materials.insert(name, description.into_material());
+            }
+            current = Some((name.to_string(), MtlDescription::default()));
+            continue;
+        }
+
+        let Some((_, description)) = current.as_mut() else {
+            return Err(error(line, format!("'{}' appears before any newmtl", keyword)));
+        };
+        match keyword {
+            "Kd" => description.kd = parse_color(args).map_err(|m| error(line, m))?,
+            "Ks" => description.ks = parse_color(args).map_err(|m| error(line, m))?,
+            "Ns" => description.ns = parse_floats(args, 1, 1).map_err(|m| error(line, m))?[0],
+            "Ni" => description.ni = parse_floats(args, 1, 1).map_err(|m| error(line, m))?[0],
+            "d" => description.d = parse_floats(args, 1, 1).map_err(|m| error(line, m))?[0],
+            "Tr" => description.d = 1.0 - parse_floats(args, 1, 1).map_err(|m| error(line, m))?[0],
+            "illum" => description.illum = parse_floats(args, 1, 1).map_err(|m| error(line, m))?[0] as u32,
+            "map_Kd" => {
+                // Texture options (e.g. -s, -o) may precede the file name, which always comes last.
+                let file = args.last().ok_or_else(|| error(line, "map_Kd needs a file name".to_string()))?;
+                let texture_path = path.parent().unwrap_or_else(|| Path::new("")).join(file);
+                let texture = ImageTexture::from_path(&texture_path)
+                    .map_err(|e| error(line, format!("failed to load texture {}: {}", texture_path.display(), e)))?;
+                description.map_kd = Some(Arc::new(texture));
+            }
+            // Remaining statements (ambient color, emission, other maps) have no equivalent here.
+            _ => {}
+        }
+    }
+
+    if let Some((name, description)) = current {
+        materials.insert(name, description.into_material());
+    }
+    Ok(materials)
+}
+
+// Faces that share a material, along with the material name and the line of its usemtl.
+struct FaceGroup {
+    material: Option<(String, usize)>,
+    faces: Vec<[VertexIndex; 3]>,
+}
+
+struct MtlDescription {
+    kd: Vector3,
+    ks: Vector3,
+    ns: f64,
+    ni: f64,
+    d: f64,
+    illum: u32,
+    map_kd: Option<Arc<ImageTexture>>,
+}
+
+impl Default for MtlDescription {
+    fn default() -> Self {
+        MtlDescription {
+            kd: Vector3::new(0.8, 0.8, 0.8),
+            ks: Vector3::new(0.0, 0.0, 0.0),
+            ns: 0.0,
+            ni: 1.0,
+            d: 1.0,
+            illum: 2,
+            map_kd: None,
+        }
+    }
+}
+
+impl MtlDescription {
+    fn into_material(self) -> Arc<dyn Material> {
+        // Transparent materials become glass; materials with reflection enabled (illum 3) or
+        // with only a specular color become metal, using the Phong exponent to pick the fuzz.
+        let is_transparent = self.d < 1.0 || matches!(self.illum, 4 | 6 | 7);
+        let is_black = |c: Vector3| c.x() <= 0.0 && c.y() <= 0.0 && c.z() <= 0.0;
+        if is_transparent {
+            Arc::new(Dielectric::new(if self.ni > 1.0 { self.ni } else { 1.5 }))
+        } else if !is_black(self.ks) && (self.illum == 3 || (is_black(self.kd) && self.map_kd.is_none())) {
+            let fuzz = (2.0 / (self.ns + 2.0)).sqrt();
+            Arc::new(Metal::new(self.ks, fuzz))
+        } else if let Some(texture) = self.map_kd {
+            Arc::new(Lambertian::new_from_texture(texture))
+        } else {
+            Arc::new(Lambertian::new(self.kd))
+        }
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    fs::read_to_string(path).map_err(|source| ObjError::Io { path: path.to_path_buf(), source })
+}
+
+fn tokenize(line: &str) -> Vec<&str> {
+    let content = match line.find('#') {
+        Some(comment) => &line[..comment],
+        None => line,
+    };
+    content.split_whitespace().collect()
+}
+
+fn parse_floats(args: &[&str], min: usize, max: usize) -> Result<Vec<f64>, String> {
+    if args.len() < min || args.len() > max {
+        let expected = if min == max { format!("{}", min) } else { format!("{} to {}", min, max) };
+        return Err(format!("expected {} numbers, found {}", expected, args.len()));
+    }
+    args.iter()
+        .map(|arg| arg.parse::<f64>().map_err(|_| format!("invalid number '{}'", arg)))
+        .collect()
+}
+
+fn parse_color(args: &[&str]) -> Result<Vector3, String> {
+    // A single value is shorthand for a gray color.
+    let c = parse_floats(args, 1, 3)?;
+    Ok(match c.len() {
+        3 => Vector3::new(c[0], c[1], c[2]),
+        1 => Vector3::new(c[0], c[0], c[0]),
+        _ => return Err(format!("expected 1 or 3 color components, found {}", c.len())),
+    })
+}
+
+fn parse_face_vertex(corner: &str, buffers: &MeshBuffers) -> Result<VertexIndex, String> {
+    // Corners look like v, v/vt, v//vn or v/vt/vn.
+    let mut parts = corner.split('/');
+    let position = parse_index(parts.next().unwrap_or(""), buffers.positions.len(), "vertex")?;
+    let uv = match parts.next() {
+        Some("") | None => None,
+        Some(uv) => Some(parse_index(uv, buffers.uvs.len(), "texture coordinate")?),
+    };
+    let normal = match parts.next() {
+        Some("") | None => None,
+        Some(normal) => Some(parse_index(normal, buffers.normals.len(), "normal")?),
+    };
+    if parts.next().is_some() {
+        return Err(format!("malformed face vertex '{}'", corner));
+    }
+    Ok(VertexIndex { position, normal, uv })
+}
+
+fn parse_index(index: &str, count: usize, kind: &str) -> Result<usize, String> {
+    // OBJ indices are 1-based, and negative indices count back from the most recent element.
+    let value = index.parse::<i64>().map_err(|_| format!("invalid {} index '{}'", kind, index))?;
+    let resolved = if value > 0 { value - 1 } else { count as i64 + value };
+    if value == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("{} index {} is out of range ({} defined)", kind, value, count));
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittable::{HitRecord, Hittable};
+    use crate::ray::Ray;
+    use crate::test_util::TempDir;
+
+    // Writes the given files into a fresh directory, which lives as long as the returned TempDir.
+    fn write_files(files: &[(&str, &str)]) -> TempDir {
+        let directory = TempDir::new();
+        for (file, contents) in files {
+            directory.write(file, contents);
+        }
+        directory
+    }
+
+    fn parse_error(result: Result<impl Sized, ObjError>) -> (PathBuf, usize, String) {
+        match result {
+            Err(ObjError::Parse { path, line, message }) => (path, line, message),
+            Err(e) => panic!("expected a parse error, got {}", e),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    // Whether a ray straight down the z axis through (x, y) hits the world.
+    fn hits_at(world: &HittableList, x: f64, y: f64) -> bool {
+        let ray = Ray::new(Vector3::new(x, y, 1.0), Vector3::new(0.0, 0.0, -1.0), 0.0);
+        world.hit(&ray, 0.001, f64::INFINITY, &mut HitRecord::default())
+    }
+
+    #[test]
+    fn loads_polygons_as_triangle_fans() {
+        let directory = write_files(&[("quad.obj", "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n")]);
+        let meshes = load_obj(directory.path().join("quad.obj")).unwrap();
+        assert_eq!(meshes.len(), 1);
+        // The fan splits the quad along its diagonal from (0, 0) to (1, 1).
+        assert!(hits_at(&meshes, 0.75, 0.25));
+        assert!(hits_at(&meshes, 0.25, 0.75));
+        assert!(!hits_at(&meshes, 1.25, 0.5));
+    }
+
+    #[test]
+    fn resolves_negative_indices_against_vertices_so_far() {
+        // The face refers to the four vertices before it, not to the one defined after it.
+        let source = "v 5 5 0\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf -4 -3 -2 -1\nv 9 9 0\n";
+        let directory = write_files(&[("relative.obj", source)]);
+        let meshes = load_obj(directory.path().join("relative.obj")).unwrap();
+        assert!(hits_at(&meshes, 0.75, 0.25));
+        assert!(hits_at(&meshes, 0.25, 0.75));
+        assert!(!hits_at(&meshes, 2.0, 2.0));
+    }
+
+    #[test]
+    fn reports_line_of_out_of_range_negative_index() {
+        let directory = write_files(&[("bad.obj", "v 0 0 0\nv 1 0 0\nv 1 1 0\nf -1 -2 -4\n")]);
+        let (_, line, message) = parse_error(load_obj(directory.path().join("bad.obj")));
+        assert_eq!(line, 4);
+        assert!(message.contains("out of range"), "{}", message);
+    }
+
+    #[test]
+    fn reports_line_of_invalid_number() {
+        let directory = write_files(&[("bad.obj", "# comment\nv 0 0 0\nv 1 x 0\n")]);
+        let (path, line, message) = parse_error(load_obj(directory.path().join("bad.obj")));
+        assert_eq!(path, directory.path().join("bad.obj"));
+        assert_eq!(line, 3);
+        assert!(message.contains("invalid number 'x'"), "{}", message);
+    }
+
+    #[test]
+    fn reports_line_of_out_of_range_face_index() {
+        let directory = write_files(&[("bad.obj", "v 0 0 0\nv 1 0 0\nv 1 1 0\n\nf 1 2 4\n")]);
+        let (_, line, message) = parse_error(load_obj(directory.path().join("bad.obj")));
+        assert_eq!(line, 5);
+        assert!(message.contains("vertex index 4 is out of range"), "{}", message);
+    }
+
+    #[test]
+    fn reports_usemtl_line_of_undefined_material() {
+        let source = "mtllib scene.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nusemtl missing\nf 1 2 3\n";
+        let directory = write_files(&[("bad.obj", source), ("scene.mtl", "newmtl red\nKd 1 0 0\n")]);
+        let (_, line, message) = parse_error(load_obj(directory.path().join("bad.obj")));
+        assert_eq!(line, 5);
+        assert!(message.contains("'missing'"), "{}", message);
+    }
+
+    #[test]
+    fn reports_mtl_errors_in_the_library_file() {
+        let directory = write_files(&[("bad.obj", "mtllib bad.mtl\n"), ("bad.mtl", "newmtl red\nKd 1 0\n")]);
+        let (path, line, message) = parse_error(load_obj(directory.path().join("bad.obj")));
+        assert_eq!(path, directory.path().join("bad.mtl"));
+        assert_eq!(line, 2);
+        assert!(message.contains("expected 1 or 3 color components"), "{}", message);
+    }
+
+    #[test]
+    fn reports_statements_before_newmtl() {
+        let directory = write_files(&[("bad.mtl", "\nNs 10\n")]);
+        let (_, line, message) = parse_error(load_mtl(directory.path().join("bad.mtl")));
+        assert_eq!(line, 2);
+        assert!(message.contains("before any newmtl"), "{}", message);
+    }
+}
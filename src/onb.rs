@@ -0,0 +1,42 @@
+use crate::vector3::Vector3;
+
+// Orthonormal basis with w along a given direction.
+#[derive(Clone, Copy, Debug)]
+pub struct Onb {
+    u: Vector3,
+    v: Vector3,
+    w: Vector3,
+}
+
+impl Onb {
+    pub fn new(n: Vector3) -> Self {
+        let w = n.unit_vector();
+        let a = if w.x().abs() > 0.9 { Vector3::new(0.0, 1.0, 0.0) } else { Vector3::new(1.0, 0.0, 0.0) };
+        let v = w.cross(a).unit_vector();
+        let u = w.cross(v);
+        Onb { u, v, w }
+    }
+    // Basis with w along n and u along the part of tangent perpendicular to n, falling back to
+    // an arbitrary u when tangent is zero or parallel to n.
+    pub fn from_normal_tangent(n: Vector3, tangent: Vector3) -> Self {
+        let w = n.unit_vector();
+        let u = tangent - tangent.dot(w) * w;
+        if u.near_zero() {
+            return Onb::new(n);
+        }
+        let u = u.unit_vector();
+        Onb { u, v: w.cross(u), w }
+    }
+    pub fn u(&self) -> Vector3 { self.u }
+    pub fn v(&self) -> Vector3 { self.v }
+    pub fn w(&self) -> Vector3 { self.w }
+
+    // Transform from basis coordinates to world space.
+    pub fn transform(&self, v: Vector3) -> Vector3 {
+        (v.x() * self.u) + (v.y() * self.v) + (v.z() * self.w)
+    }
+    // Transform from world space to basis coordinates.
+    pub fn to_local(&self, v: Vector3) -> Vector3 {
+        Vector3::new(v.dot(self.u), v.dot(self.v), v.dot(self.w))
+    }
+}
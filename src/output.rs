@@ -0,0 +1,244 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use exr::prelude::{f16, AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, SmallVec, Vec2, WritableImage};
+use image::codecs::hdr::HdrEncoder;
+use image::{ImageError, ImageFormat, Rgb};
+
+use crate::framebuffer::Framebuffer;
+use crate::tonemap::PostProcess;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io { path: PathBuf, source: io::Error },
+    Image { path: PathBuf, source: ImageError },
+    Exr { path: PathBuf, source: exr::error::Error },
+    UnsupportedFormat { path: PathBuf },
+    SizeMismatch { path: PathBuf, layer: String },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SaveError::Image { path, source } => write!(f, "{}: {}", path.display(), source),
+            SaveError::Exr { path, source } => write!(f, "{}: {}", path.display(), source),
+            SaveError::UnsupportedFormat { path } => write!(f, "{}: unsupported image format", path.display()),
+            SaveError::SizeMismatch { path, layer } => {
+                write!(f, "{}: layer \"{}\" does not match the size of the first layer", path.display(), layer)
+            }
+        }
+    }
+}
+
+impl Error for SaveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SaveError::Io { source, .. } => Some(source),
+            SaveError::Image { source, .. } => Some(source),
+            SaveError::Exr { source, .. } => Some(source),
+            SaveError::UnsupportedFormat { .. } | SaveError::SizeMismatch { .. } => None,
+        }
+    }
+}
+
+// Storage type for the channels of an OpenEXR file. Half precision files are half the size,
+// with about three significant digits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExrPrecision {
+    Half,
+    #[default]
+    Float,
+}
+
+// Saves the framebuffer in the format given by the file extension. OpenEXR (.exr), Radiance
+// (.hdr) and PFM (.pfm) files keep the linear radiance values, with OpenEXR channels stored at
+// exr_precision; any other format the image crate knows, such as PNG or JPEG, is written with
+// 8 bits per channel after the post-process.
+pub fn save(buffer: &Framebuffer, path: &Path, post: &PostProcess, exr_precision: ExrPrecision) -> Result<(), SaveError> {
+    let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("exr") => save_exr(&[("", buffer)], path, exr_precision),
+        Some("hdr") => save_hdr(buffer, path),
+        Some("pfm") => save_pfm(buffer, path),
+        Some(_) => buffer.to_rgb_image_with(post).save(path).map_err(|source| match source {
+            ImageError::Unsupported(_) => SaveError::UnsupportedFormat { path: path.to_path_buf() },
+            source => SaveError::Image { path: path.to_path_buf(), source },
+        }),
+        None => Err(SaveError::UnsupportedFormat { path: path.to_path_buf() }),
+    }
+}
+
+// Checks that save can write the format given by the file extension, so that callers can reject
+// an unsupported one before spending time on a render.
+pub fn check_format(path: &Path) -> Result<(), SaveError> {
+    let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+    let supported = match extension.as_deref() {
+        Some("exr") | Some("hdr") | Some("pfm") => true,
+        _ => ImageFormat::from_path(path).is_ok_and(|format| format.can_write() && format.writing_enabled()),
+    };
+    if supported { Ok(()) } else { Err(SaveError::UnsupportedFormat { path: path.to_path_buf() }) }
+}
+
+// Writes any number of framebuffers of the same size into one OpenEXR file, each as a group of
+// R, G and B channels prefixed by its name (e.g. "albedo.R"). An empty name gives the plain R, G
+// and B channels that viewers show by default.
+pub fn save_exr(layers: &[(&str, &Framebuffer)], path: &Path, precision: ExrPrecision) -> Result<(), SaveError> {
+    let Some(&(_, first)) = layers.first() else {
+        return Err(SaveError::UnsupportedFormat { path: path.to_path_buf() });
+    };
+    let (width, height) = (first.width() as usize, first.height() as usize);
+
+    let mut channels = Vec::new();
+    for &(name, buffer) in layers {
+        if buffer.width() != first.width() || buffer.height() != first.height() {
+            return Err(SaveError::SizeMismatch { path: path.to_path_buf(), layer: name.to_string() });
+        }
+        for (component, suffix) in ["R", "G", "B"].into_iter().enumerate() {
+            let values = buffer.pixels().iter().map(|pixel| pixel[component]);
+            let samples = match precision {
+                ExrPrecision::Half => FlatSamples::F16(values.map(f16::from_f32).collect()),
+                ExrPrecision::Float => FlatSamples::F32(values.collect()),
+            };
+            let channel_name = if name.is_empty() { suffix.to_string() } else { format!("{}.{}", name, suffix) };
+            channels.push(AnyChannel::new(channel_name.as_str(), samples));
+        }
+    }
+
+    let layer = Layer::new(
+        Vec2(width, height),
+        LayerAttributes::default(),
+        Encoding::FAST_LOSSLESS,
+        AnyChannels::sort(SmallVec::from_vec(channels)),
+    );
+    Image::from_layer(layer)
+        .write()
+        .to_file(path)
+        .map_err(|source| SaveError::Exr { path: path.to_path_buf(), source })
+}
+
+// Radiance RGBE, stored with a shared 8-bit exponent per pixel.
+pub fn save_hdr(buffer: &Framebuffer, path: &Path) -> Result<(), SaveError> {
+    let pixels: Vec<Rgb<f32>> = buffer.pixels().iter().map(|&pixel| Rgb(pixel)).collect();
+    let file = create_file(path)?;
+    HdrEncoder::new(file)
+        .encode(&pixels, buffer.width() as usize, buffer.height() as usize)
+        .map_err(|source| SaveError::Image { path: path.to_path_buf(), source })
+}
+
+// Portable float map: a short text header followed by little-endian 32-bit floats, with the
+// rows stored from the bottom of the image up.
+pub fn save_pfm(buffer: &Framebuffer, path: &Path) -> Result<(), SaveError> {
+    let io_error = |source| SaveError::Io { path: path.to_path_buf(), source };
+    let mut file = create_file(path)?;
+
+    // A negative scale marks the data as little-endian.
+    write!(file, "PF\n{} {}\n-1.0\n", buffer.width(), buffer.height()).map_err(io_error)?;
+    let width = buffer.width() as usize;
+    for row in buffer.pixels().chunks(width.max(1)).rev() {
+        for value in row.iter().flatten() {
+            file.write_all(&value.to_le_bytes()).map_err(io_error)?;
+        }
+    }
+    file.flush().map_err(io_error)
+}
+
+fn create_file(path: &Path) -> Result<BufWriter<File>, SaveError> {
+    File::create(path)
+        .map(BufWriter::new)
+        .map_err(|source| SaveError::Io { path: path.to_path_buf(), source })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    use exr::prelude::{read_all_flat_layers_from_file, FlatImage};
+
+    use crate::test_util::TempDir;
+    use crate::vector3::Vector3;
+
+    // A 3x2 image whose pixels all differ, with values outside [0, 1].
+    fn test_buffer() -> Framebuffer {
+        let mut buffer = Framebuffer::new(3, 2);
+        for y in 0..2 {
+            for x in 0..3 {
+                let i = (y * 3 + x) as f64;
+                buffer.set_pixel(x, y, Vector3::new(0.25 * i, 10.0 + i, 1.0 / (1.0 + i)));
+            }
+        }
+        buffer
+    }
+
+    #[test]
+    fn pfm_has_header_and_little_endian_rows_from_the_bottom() {
+        let buffer = test_buffer();
+        let directory = TempDir::new();
+        let path = directory.path().join("test.pfm");
+        save(&buffer, &path, &PostProcess::default(), ExrPrecision::default()).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        let header = b"PF\n3 2\n-1.0\n";
+        assert_eq!(&bytes[..header.len()], header);
+        let values: Vec<f32> = bytes[header.len()..]
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(values.len(), 3 * 2 * 3);
+
+        let bottom_up: Vec<f32> = buffer.pixels().chunks(3).rev().flatten().flatten().copied().collect();
+        assert_eq!(values, bottom_up);
+    }
+
+    #[test]
+    fn hdr_round_trips_within_rgbe_precision() {
+        let buffer = test_buffer();
+        let directory = TempDir::new();
+        let path = directory.path().join("test.hdr");
+        save(&buffer, &path, &PostProcess::default(), ExrPrecision::default()).unwrap();
+
+        let image = image::open(&path).unwrap().into_rgb32f();
+        assert_eq!(image.dimensions(), (3, 2));
+        for (x, y, pixel) in image.enumerate_pixels() {
+            let expected = buffer.get_pixel(x, y);
+            // The shared exponent leaves 8 bits of mantissa for the brightest channel.
+            let tolerance = expected.x().max(expected.y()).max(expected.z()) / 128.0;
+            for (value, expected) in pixel.0.iter().zip([expected.x(), expected.y(), expected.z()]) {
+                assert!((*value as f64 - expected).abs() <= tolerance, "{} vs {} at ({}, {})", value, expected, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn exr_stores_the_requested_precision() {
+        let buffer = test_buffer();
+        let directory = TempDir::new();
+        for (precision, name) in [(ExrPrecision::Half, "half.exr"), (ExrPrecision::Float, "float.exr")] {
+            let path = directory.path().join(name);
+            save(&buffer, &path, &PostProcess::default(), precision).unwrap();
+
+            let image: FlatImage = read_all_flat_layers_from_file(&path).unwrap();
+            let channels = &image.layer_data[0].channel_data.list;
+            assert_eq!(channels.len(), 3);
+            for channel in channels {
+                let is_half = matches!(channel.sample_data, FlatSamples::F16(_));
+                assert_eq!(is_half, precision == ExrPrecision::Half, "{:?}", precision);
+            }
+        }
+    }
+
+    #[test]
+    fn checks_format_before_saving() {
+        for name in ["image.png", "image.JPG", "image.exr", "image.hdr", "image.pfm"] {
+            assert!(check_format(Path::new(name)).is_ok(), "{}", name);
+        }
+        for name in ["image.xyz", "image.dds", "image"] {
+            assert!(matches!(check_format(Path::new(name)), Err(SaveError::UnsupportedFormat { .. })), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::hittable::Hittable;
+use crate::onb::Onb;
+use crate::sampler::{sample_cosine_hemisphere, sample_uniform_sphere, Sampler};
+use crate::vector3::Vector3;
+
+// Probability density over directions, as a function of solid angle.
+pub trait Pdf: Send + Sync {
+    fn value(&self, direction: Vector3) -> f64;
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vector3;
+}
+
+pub struct SpherePdf;
+
+impl Pdf for SpherePdf {
+    fn value(&self, _direction: Vector3) -> f64 {
+        1.0 / (4.0 * PI)
+    }
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vector3 {
+        sample_uniform_sphere(sampler.get_2d())
+    }
+}
+
+pub struct CosinePdf {
+    uvw: Onb
+}
+
+impl CosinePdf {
+    pub fn new(w: Vector3) -> Self { CosinePdf { uvw: Onb::new(w) } }
+}
+
+impl Pdf for CosinePdf {
+    fn value(&self, direction: Vector3) -> f64 {
+        let cosine_theta = direction.unit_vector().dot(self.uvw.w());
+        (cosine_theta / PI).max(0.0)
+    }
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vector3 {
+        self.uvw.transform(sample_cosine_hemisphere(sampler.get_2d()))
+    }
+}
+
+// Samples directions from an origin toward a hittable, e.g. a list of lights.
+pub struct HittablePdf {
+    objects: Arc<dyn Hittable>,
+    origin: Vector3,
+}
+
+impl HittablePdf {
+    pub fn new(objects: Arc<dyn Hittable>, origin: Vector3) -> Self { HittablePdf { objects, origin } }
+}
+
+impl Pdf for HittablePdf {
+    fn value(&self, direction: Vector3) -> f64 {
+        self.objects.pdf_value(self.origin, direction)
+    }
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vector3 {
+        self.objects.random(self.origin, sampler)
+    }
+}
+
+// Equal-weight mixture of two densities.
+pub struct MixturePdf {
+    p: [Arc<dyn Pdf>; 2]
+}
+
+impl MixturePdf {
+    pub fn new(p0: Arc<dyn Pdf>, p1: Arc<dyn Pdf>) -> Self { MixturePdf { p: [p0, p1] } }
+}
+
+impl Pdf for MixturePdf {
+    fn value(&self, direction: Vector3) -> f64 {
+        0.5 * self.p[0].value(direction) + 0.5 * self.p[1].value(direction)
+    }
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vector3 {
+        if sampler.get_1d() < 0.5 {
+            self.p[0].generate(sampler)
+        } else {
+            self.p[1].generate(sampler)
+        }
+    }
+}
+
+// Mixture of densities with weights summing to one.
+pub struct WeightedMixturePdf {
+    pdfs: Vec<(f64, Arc<dyn Pdf>)>
+}
+
+impl WeightedMixturePdf {
+    pub fn new(pdfs: Vec<(f64, Arc<dyn Pdf>)>) -> Self { WeightedMixturePdf { pdfs } }
+}
+
+impl Pdf for WeightedMixturePdf {
+    fn value(&self, direction: Vector3) -> f64 {
+        self.pdfs.iter().map(|(weight, pdf)| weight * pdf.value(direction)).sum()
+    }
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vector3 {
+        let mut u = sampler.get_1d();
+        for (weight, pdf) in &self.pdfs[..self.pdfs.len() - 1] {
+            if u < *weight {
+                return pdf.generate(sampler);
+            }
+            u -= weight;
+        }
+        self.pdfs[self.pdfs.len() - 1].1.generate(sampler)
+    }
+}
@@ -1,4 +1,4 @@
-use rand::{rngs::ThreadRng, Rng};
+use crate::rng::Rng;
 use crate::vector3::Vector3;
 
 const POINT_COUNT: usize = 256;
@@ -12,20 +12,17 @@ pub struct Perlin {
 }
 
 impl Perlin {
-    pub fn new() -> Self {
-        let mut rng = rand::thread_rng();
-        let mut randvec = vec![Vector3::new(0.0, 0.0, 0.0); POINT_COUNT];
-        for i in 0..POINT_COUNT {
-            randvec[i] = Vector3::random_unit_vector();
-        }
+    pub fn new(rng: &mut Rng) -> Self {
+        let randvec = (0..POINT_COUNT).map(|_| Vector3::random_unit_vector(rng)).collect();
 
-        let perm_x = Perlin::generate_perm(&mut rng);
-        let perm_y = Perlin::generate_perm(&mut rng);
-        let perm_z = Perlin::generate_perm(&mut rng);
+        let perm_x = Perlin::generate_perm(rng);
+        let perm_y = Perlin::generate_perm(rng);
+        let perm_z = Perlin::generate_perm(rng);
 
         Perlin { randvec, perm_x, perm_y, perm_z }
     }
 
+    #[allow(clippy::needless_range_loop)]
     pub fn noise(&self, p: Vector3) -> f64 {
         let u = p.x() - p.x().floor();
         let v = p.y() - p.y().floor();
@@ -65,19 +62,20 @@ impl Perlin {
         accum.abs()
     }
 
-    fn generate_perm(rng: &mut ThreadRng) -> Vec<usize> {
+    fn generate_perm(rng: &mut Rng) -> Vec<usize> {
         let mut p = (0..POINT_COUNT).collect::<Vec<_>>();
         Perlin::permute(&mut p, POINT_COUNT, rng);
         p
     }
 
-    fn permute(p: &mut [usize], n: usize, rng: &mut ThreadRng) {
+    fn permute(p: &mut [usize], n: usize, rng: &mut Rng) {
         for i in (1..n).rev() {
-            let target = rng.gen_range(0..i+1);
+            let target = rng.random_index(i + 1);
             p.swap(i, target);
         }
     }
 
+    #[allow(clippy::needless_range_loop)]
     fn trilinear_interp(c: [[[Vector3; 2]; 2]; 2] , u:f64, v:f64, w:f64) -> f64 {
         let uu = u*u*(3.0-2.0*u);
         let vv = v*v*(3.0-2.0*v);
@@ -87,10 +85,10 @@ impl Perlin {
             for j in 0..2 {
                 for k in 0..2 {
                     let weight_v = Vector3::new(u - i as f64, v - j as f64, w - k as f64);
-                    accum = accum + ((i as f64)*uu + (1.0 - i as f64)*(1.0-uu))
-                                  * ((j as f64)*vv + (1.0 - j as f64)*(1.0-vv))
-                                  * ((k as f64)*ww + (1.0 - k as f64)*(1.0-ww))
-                                  * c[i][j][k].dot(weight_v);
+                    accum += ((i as f64)*uu + (1.0 - i as f64)*(1.0-uu))
+                             * ((j as f64)*vv + (1.0 - j as f64)*(1.0-vv))
+                             * ((k as f64)*ww + (1.0 - k as f64)*(1.0-ww))
+                             * c[i][j][k].dot(weight_v);
                 }
             }
         }
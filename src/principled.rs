@@ -0,0 +1,327 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::microfacet::{fresnel_dielectric, fresnel_schlick, generalized_half_vector, MicrofacetDielectricPdf, TrowbridgeReitz};
+use crate::onb::Onb;
+use crate::pdf::{CosinePdf, Pdf, WeightedMixturePdf};
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::texture::{SolidColor, Texture};
+use crate::vector3::Vector3;
+
+// Disney's principled BSDF (Burley 2012, with transmission as in Burley 2015): a single
+// material covering plastics, metals, cloth, lacquer and glass through parameters in [0, 1],
+// any of which can come from a texture. Scalar parameters read the average of the texture's
+// channels. Construct with a base color and set the other fields to change their defaults.
+pub struct Principled {
+    pub base_color: Arc<dyn Texture>,
+    // Blends from a dielectric to a metal reflecting with the base color.
+    pub metallic: Arc<dyn Texture>,
+    pub roughness: Arc<dyn Texture>,
+    // Dielectric reflectance at normal incidence, where 0.5 is 4%.
+    pub specular: Arc<dyn Texture>,
+    // Tints dielectric reflection toward the base color.
+    pub specular_tint: Arc<dyn Texture>,
+    // Extra reflection at grazing angles, for cloth.
+    pub sheen: Arc<dyn Texture>,
+    pub sheen_tint: Arc<dyn Texture>,
+    // A second, colorless and glossier specular layer on top, like a lacquer.
+    pub clearcoat: Arc<dyn Texture>,
+    pub clearcoat_gloss: Arc<dyn Texture>,
+    // Fraction of the dielectric part refracting through the surface instead of being diffused.
+    pub transmission: Arc<dyn Texture>,
+    // Index of refraction of transmitted light; not limited to [0, 1].
+    pub ior: Arc<dyn Texture>,
+    // Stretches highlights along the surface's u direction.
+    pub anisotropic: Arc<dyn Texture>,
+    // Turns the direction of anisotropy about the normal, in fractions of a full turn.
+    pub anisotropic_rotation: Arc<dyn Texture>,
+}
+
+impl Principled {
+    pub fn new(base_color: Vector3) -> Self {
+        Principled::new_from_texture(Arc::new(SolidColor::new(base_color)))
+    }
+    pub fn new_from_texture(base_color: Arc<dyn Texture>) -> Self {
+        let constant = |value: f64| -> Arc<dyn Texture> { Arc::new(SolidColor::from_rgb(value, value, value)) };
+        Principled {
+            base_color,
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            specular_tint: constant(0.0),
+            sheen: constant(0.0),
+            sheen_tint: constant(0.5),
+            clearcoat: constant(0.0),
+            clearcoat_gloss: constant(1.0),
+            transmission: constant(0.0),
+            ior: constant(1.5),
+            anisotropic: constant(0.0),
+            anisotropic_rotation: constant(0.0),
+        }
+    }
+
+    // Shading frame with u along the direction of anisotropy, which isotropic lobes ignore.
+    fn frame(&self, rec: &HitRecord) -> Onb {
+        let uvw = Onb::from_normal_tangent(rec.normal, rec.tangent);
+        let angle = 2.0 * PI * self.anisotropic_rotation.scalar_value(rec.u, rec.v, &rec.p);
+        Onb::from_normal_tangent(rec.normal, angle.cos() * uvw.u() + angle.sin() * uvw.v())
+    }
+
+    fn lobes(&self, rec: &HitRecord) -> Lobes {
+        let (u, v, p) = (rec.u, rec.v, &rec.p);
+        let scalar = |texture: &Arc<dyn Texture>| texture.scalar_value(u, v, p).clamp(0.0, 1.0);
+        let lerp = |t: f64, a: Vector3, b: Vector3| (1.0 - t) * a + t * b;
+
+        let base_color = self.base_color.value(u, v, p);
+        let metallic = scalar(&self.metallic);
+        let roughness = scalar(&self.roughness);
+        let transmission = scalar(&self.transmission);
+        let ior = self.ior.scalar_value(u, v, p).max(1.0);
+
+        // Hue and saturation of the base color at unit luminance, for the tints.
+        let luminance = 0.3 * base_color.x() + 0.6 * base_color.y() + 0.1 * base_color.z();
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        let tint = if luminance > 0.0 { base_color / luminance } else { white };
+
+        let aspect = (1.0 - 0.9 * scalar(&self.anisotropic)).sqrt();
+        let alpha = roughness * roughness;
+        let clearcoat_gloss = scalar(&self.clearcoat_gloss);
+        Lobes {
+            base_color,
+            roughness,
+            diffuse_weight: (1.0 - metallic) * (1.0 - transmission),
+            sheen: scalar(&self.sheen) * lerp(scalar(&self.sheen_tint), white, tint),
+            specular_color: lerp(metallic, 0.08 * scalar(&self.specular) * lerp(scalar(&self.specular_tint), white, tint), base_color),
+            distribution: TrowbridgeReitz::anisotropic((alpha / aspect).max(1e-3), (alpha * aspect).max(1e-3)),
+            clearcoat: scalar(&self.clearcoat),
+            clearcoat_alpha: (1.0 - clearcoat_gloss) * 0.1 + clearcoat_gloss * 0.001,
+            transmission_weight: (1.0 - metallic) * transmission,
+            eta: if rec.front_face { ior } else { 1.0 / ior },
+        }
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, r_in: &Ray, rec: &HitRecord, srec: &mut ScatterRecord, _sampler: &mut dyn Sampler) -> bool {
+        let lobes = self.lobes(rec);
+        let uvw = self.frame(rec);
+        let wo = -r_in.dir().unit_vector();
+
+        // Sample every lobe that's present, with specular reflection always there.
+        let weights = [lobes.diffuse_weight, 1.0, 0.25 * lobes.clearcoat, lobes.transmission_weight];
+        let total: f64 = weights.iter().sum();
+        let pdfs: [Arc<dyn Pdf>; 4] = [
+            Arc::new(CosinePdf::new(rec.normal)),
+            Arc::new(SpecularPdf::new(uvw, wo, lobes.distribution)),
+            Arc::new(ClearcoatPdf::new(rec.normal, wo, lobes.clearcoat_alpha)),
+            Arc::new(MicrofacetDielectricPdf::with_frame(uvw, wo, lobes.distribution, lobes.eta)),
+        ];
+        let mixture = weights.into_iter().zip(pdfs).filter(|(weight, _)| *weight > 0.0).map(|(weight, pdf)| (weight / total, pdf));
+
+        srec.attenuation = lobes.base_color;
+        srec.pdf = Some(Arc::new(WeightedMixturePdf::new(mixture.collect())));
+        srec.skip_pdf = false;
+        true
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, scattered: &Ray) -> Vector3 {
+        let lobes = self.lobes(rec);
+        let uvw = self.frame(rec);
+        let wo = uvw.to_local(-r_in.dir().unit_vector());
+        let wi = uvw.to_local(scattered.dir().unit_vector());
+        if wo.z() <= 0.0 || wi.z() == 0.0 {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+        if wi.z() > 0.0 {
+            lobes.reflection(wo, wi) * wi.z()
+        } else {
+            lobes.transmission(wo, wi) * -wi.z()
+        }
+    }
+}
+
+// Principled parameters at a hit point, as the colors and weights of each lobe.
+struct Lobes {
+    base_color: Vector3,
+    roughness: f64,
+    diffuse_weight: f64,
+    sheen: Vector3,
+    specular_color: Vector3,
+    distribution: TrowbridgeReitz,
+    clearcoat: f64,
+    clearcoat_alpha: f64,
+    transmission_weight: f64,
+    // Relative index of refraction across the surface, from the side of wo.
+    eta: f64,
+}
+
+impl Lobes {
+    // BSDF for wo and wi both above the surface.
+    fn reflection(&self, wo: Vector3, wi: Vector3) -> Vector3 {
+        let wh = (wo + wi).unit_vector();
+        let cos_theta_d = wi.dot(wh);
+
+        // Burley diffuse with retro-reflection at grazing angles for rough surfaces, plus sheen.
+        let fl = schlick_weight(wi.z());
+        let fv = schlick_weight(wo.z());
+        let fd90 = 0.5 + 2.0 * self.roughness * cos_theta_d * cos_theta_d;
+        let fd = (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv);
+        let diffuse = self.diffuse_weight * (self.base_color * (fd / PI) + self.sheen * schlick_weight(cos_theta_d));
+
+        // The transmissive part reflects what the transmission lobe doesn't refract, so that
+        // glass keeps total internal reflection.
+        let dielectric = fresnel_dielectric(cos_theta_d, self.eta);
+        let fresnel = (1.0 - self.transmission_weight) * fresnel_schlick(cos_theta_d, self.specular_color)
+            + Vector3::new(1.0, 1.0, 1.0) * (self.transmission_weight * dielectric);
+        let specular = fresnel * (self.distribution.d(wh) * self.distribution.g(wo, wi) / (4.0 * wo.z() * wi.z()));
+
+        let clearcoat = if self.clearcoat > 0.0 {
+            let fresnel = 0.04 + 0.96 * schlick_weight(cos_theta_d);
+            let g = smith_g1_ggx(wo.z(), 0.25) * smith_g1_ggx(wi.z(), 0.25);
+            0.25 * self.clearcoat * gtr1(wh.z(), self.clearcoat_alpha) * fresnel * g / (4.0 * wo.z() * wi.z())
+        } else {
+            0.0
+        };
+
+        diffuse + specular + Vector3::new(clearcoat, clearcoat, clearcoat)
+    }
+
+    // BSDF for wo above and wi below the surface: rough dielectric transmission, tinted by the
+    // base color, with the square root as light usually crosses two surfaces.
+    fn transmission(&self, wo: Vector3, wi: Vector3) -> Vector3 {
+        if self.transmission_weight <= 0.0 {
+            return Vector3::new(0.0, 0.0, 0.0);
+        }
+        let Some(wm) = generalized_half_vector(wo, wi, self.eta) else {
+            return Vector3::new(0.0, 0.0, 0.0);
+        };
+        let fresnel = fresnel_dielectric(wo.dot(wm), self.eta);
+        let denom = wi.dot(wm) + wo.dot(wm) / self.eta;
+        let f = self.distribution.d(wm) * self.distribution.g(wo, wi) * (1.0 - fresnel)
+            * (wi.dot(wm) * wo.dot(wm) / (wi.z() * wo.z() * denom * denom)).abs()
+            / (self.eta * self.eta);
+        let tint = Vector3::new(self.base_color.x().sqrt(), self.base_color.y().sqrt(), self.base_color.z().sqrt());
+        self.transmission_weight * f * tint
+    }
+}
+
+fn schlick_weight(cos_theta: f64) -> f64 { (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5) }
+
+// Generalized Trowbridge-Reitz with exponent 1, the long tailed distribution of the clearcoat.
+fn gtr1(cos_theta_h: f64, alpha: f64) -> f64 {
+    if alpha >= 1.0 {
+        return 1.0 / PI;
+    }
+    let alpha2 = alpha * alpha;
+    (alpha2 - 1.0) / (PI * alpha2.ln() * (1.0 + (alpha2 - 1.0) * cos_theta_h * cos_theta_h))
+}
+
+fn smith_g1_ggx(cos_theta: f64, alpha: f64) -> f64 {
+    let alpha2 = alpha * alpha;
+    let cos2_theta = cos_theta * cos_theta;
+    2.0 * cos_theta / (cos_theta + (alpha2 + cos2_theta - alpha2 * cos2_theta).sqrt())
+}
+
+// The reflection lobes keep the density of samples reflecting below the surface, since the
+// transmission lobe may give those directions a non-zero value, and the mixture must then
+// account for every way a direction could have been sampled.
+
+// Reflection off the visible normals of the specular lobe.
+struct SpecularPdf {
+    uvw: Onb,
+    wo: Vector3,
+    distribution: TrowbridgeReitz,
+}
+
+impl SpecularPdf {
+    fn new(uvw: Onb, wo: Vector3, distribution: TrowbridgeReitz) -> Self {
+        SpecularPdf { uvw, wo: uvw.to_local(wo.unit_vector()), distribution }
+    }
+}
+
+impl Pdf for SpecularPdf {
+    fn value(&self, direction: Vector3) -> f64 {
+        let wh = self.wo + self.uvw.to_local(direction.unit_vector());
+        if wh.length_squared() == 0.0 {
+            return 0.0;
+        }
+        let wh = wh.unit_vector();
+        if wh.z() <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.d_visible(self.wo, wh) / (4.0 * self.wo.dot(wh))
+    }
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vector3 {
+        let wh = self.distribution.sample_wm(self.wo, sampler.get_2d());
+        self.uvw.transform((-self.wo).reflect(wh))
+    }
+}
+
+// Reflection off half vectors distributed as the clearcoat's GTR1.
+struct ClearcoatPdf {
+    uvw: Onb,
+    wo: Vector3,
+    alpha: f64,
+}
+
+impl ClearcoatPdf {
+    fn new(normal: Vector3, wo: Vector3, alpha: f64) -> Self {
+        let uvw = Onb::new(normal);
+        ClearcoatPdf { uvw, wo: uvw.to_local(wo.unit_vector()), alpha }
+    }
+}
+
+impl Pdf for ClearcoatPdf {
+    fn value(&self, direction: Vector3) -> f64 {
+        let wh = self.wo + self.uvw.to_local(direction.unit_vector());
+        if wh.length_squared() == 0.0 {
+            return 0.0;
+        }
+        // Reflecting about either side of a half vector gives the same direction.
+        let wh = wh.unit_vector();
+        gtr1(wh.z(), self.alpha) * wh.z().abs() / (4.0 * self.wo.dot(wh).abs())
+    }
+    fn generate(&self, sampler: &mut dyn Sampler) -> Vector3 {
+        let (u0, u1) = sampler.get_2d();
+        let alpha2 = self.alpha * self.alpha;
+        let cos_theta = ((1.0 - alpha2.powf(1.0 - u0)) / (1.0 - alpha2)).max(0.0).sqrt();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * u1;
+        let wh = Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        self.uvw.transform((-self.wo).reflect(wh))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{assert_consistent_pdf, assert_eval_matches_sampling};
+
+    fn constant(value: f64) -> Arc<dyn Texture> { Arc::new(SolidColor::from_rgb(value, value, value)) }
+
+    #[test]
+    fn specular_pdf_is_consistent() {
+        let wo = Vector3::new(0.4, 0.3, 1.0).unit_vector();
+        let uvw = Onb::new(Vector3::new(0.0, 0.0, 1.0));
+        assert_consistent_pdf(&SpecularPdf::new(uvw, wo, TrowbridgeReitz::anisotropic(0.4, 0.15)));
+    }
+
+    #[test]
+    fn clearcoat_pdf_is_consistent() {
+        let wo = Vector3::new(0.4, 0.3, 1.0).unit_vector();
+        assert_consistent_pdf(&ClearcoatPdf::new(Vector3::new(0.0, 0.0, 1.0), wo, 0.1));
+    }
+
+    #[test]
+    fn eval_matches_sampling() {
+        let mut principled = Principled::new(Vector3::new(0.8, 0.5, 0.3));
+        principled.metallic = constant(0.3);
+        principled.clearcoat = constant(1.0);
+        principled.clearcoat_gloss = constant(0.0);
+        principled.transmission = constant(0.5);
+        principled.anisotropic = constant(0.5);
+        assert_eval_matches_sampling(&principled, Vector3::new(0.5, 0.2, 1.0).unit_vector());
+    }
+}
@@ -0,0 +1,51 @@
+use indicatif::{ProgressBar, ProgressStyle};
+
+// Receives progress updates while an image renders. Calls can come from any render thread.
+pub trait ProgressReporter: Send + Sync {
+    fn start(&self, _total_pixels: u64) {}
+    fn advance(&self, _pixels: u64) {}
+    fn finish(&self) {}
+    // A line of status text, such as which file is being rendered.
+    fn message(&self, _text: &str) {}
+}
+
+// Ignores all progress updates.
+pub struct NoProgress;
+
+impl ProgressReporter for NoProgress {}
+
+// Draws a progress bar on the terminal.
+pub struct ConsoleProgress {
+    bar: ProgressBar,
+}
+
+impl ConsoleProgress {
+    pub fn new() -> Self {
+        let bar = ProgressBar::hidden();
+        bar.set_style(ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] [{bar:40.cyan/blue}]{pos:>7}/{len:7}\n{msg}\n")
+            .expect("Unable to create progress bar style."));
+        ConsoleProgress { bar }
+    }
+}
+
+impl Default for ConsoleProgress {
+    fn default() -> Self { ConsoleProgress::new() }
+}
+
+impl ProgressReporter for ConsoleProgress {
+    fn start(&self, total_pixels: u64) {
+        self.bar.reset();
+        self.bar.set_length(total_pixels);
+        self.bar.set_draw_target(indicatif::ProgressDrawTarget::stderr());
+    }
+    fn advance(&self, pixels: u64) {
+        self.bar.inc(pixels);
+    }
+    fn finish(&self) {
+        self.bar.finish();
+    }
+    fn message(&self, text: &str) {
+        self.bar.suspend(|| println!("{}", text));
+    }
+}
@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use crate::vector3::Vector3;
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable, HittableList};
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::sampler::Sampler;
+
+pub struct Quad {
+    q: Vector3,
+    u: Vector3,
+    v: Vector3,
+    w: Vector3,
+    mat: Option<Arc<dyn Material>>,
+    bbox: Aabb,
+    normal: Vector3,
+    d: f64,
+    area: f64,
+}
+
+impl Quad {
+    pub fn new(q: Vector3, u: Vector3, v: Vector3, mat: Option<Arc<dyn Material>>) -> Quad {
+        let n = u.cross(v);
+        let normal = n.unit_vector();
+        let d = normal.dot(q);
+        let w = n / n.dot(n);
+        let area = n.length();
+
+        // Compute the bounding box of all four vertices.
+        let bbox_diagonal1 = Aabb::new(q, q + u + v);
+        let bbox_diagonal2 = Aabb::new(q + u, q + v);
+        let bbox = Aabb::surrounding_box(&bbox_diagonal1, &bbox_diagonal2);
+
+        Quad { q, u, v, w, mat, bbox, normal, d, area }
+    }
+
+    fn is_interior(a: f64, b: f64) -> bool {
+        // Given the hit point in plane coordinates, return false if it is outside the
+        // primitive.
+        (0.0..=1.0).contains(&a) && (0.0..=1.0).contains(&b)
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let denom = self.normal.dot(r.dir());
+
+        // No hit if the ray is parallel to the plane.
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+
+        // Return false if the hit point parameter t is outside the ray interval.
+        let t = (self.d - self.normal.dot(r.orig())) / denom;
+        if t <= t_min || t_max <= t {
+            return false;
+        }
+
+        // Determine if the hit point lies within the planar shape using its plane coordinates.
+        let intersection = r.at(t);
+        let planar_hitpt_vector = intersection - self.q;
+        let alpha = self.w.dot(planar_hitpt_vector.cross(self.v));
+        let beta = self.w.dot(self.u.cross(planar_hitpt_vector));
+
+        if !Quad::is_interior(alpha, beta) {
+            return false;
+        }
+
+        rec.t = t;
+        rec.p = intersection;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.mat = self.mat.clone();
+        rec.set_face_normal(r, &self.normal);
+        rec.tangent = self.u.unit_vector();
+
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        *output_box = self.bbox;
+        true
+    }
+
+    fn pdf_value(&self, origin: Vector3, direction: Vector3) -> f64 {
+        // Convert the uniform density over the quad's area into a density over solid angle.
+        let mut rec = HitRecord::default();
+        if !self.hit(&Ray::new(origin, direction, 0.0), 0.001, f64::INFINITY, &mut rec) {
+            return 0.0;
+        }
+
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (direction.dot(rec.normal) / direction.length()).abs();
+
+        distance_squared / (cosine * self.area)
+    }
+
+    fn random(&self, origin: Vector3, sampler: &mut dyn Sampler) -> Vector3 {
+        let (s, t) = sampler.get_2d();
+        let p = self.q + (s * self.u) + (t * self.v);
+        p - origin
+    }
+}
+
+pub fn make_box(a: Vector3, b: Vector3, mat: Option<Arc<dyn Material>>) -> HittableList {
+    // Returns the 3D box (six sides) that contains the two opposite vertices a & b.
+    let mut sides = HittableList::new();
+
+    let min = Vector3::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Vector3::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+    let dx = Vector3::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vector3::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vector3::new(0.0, 0.0, max.z() - min.z());
+
+    sides.push(Quad::new(Vector3::new(min.x(), min.y(), max.z()), dx, dy, mat.clone()));  // front
+    sides.push(Quad::new(Vector3::new(max.x(), min.y(), max.z()), -dz, dy, mat.clone())); // right
+    sides.push(Quad::new(Vector3::new(max.x(), min.y(), min.z()), -dx, dy, mat.clone())); // back
+    sides.push(Quad::new(Vector3::new(min.x(), min.y(), min.z()), dz, dy, mat.clone()));  // left
+    sides.push(Quad::new(Vector3::new(min.x(), max.y(), max.z()), dx, -dz, mat.clone())); // top
+    sides.push(Quad::new(Vector3::new(min.x(), min.y(), min.z()), dx, dz, mat));          // bottom
+
+    sides
+}
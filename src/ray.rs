@@ -4,13 +4,18 @@ use crate::vector3::Vector3;
 pub struct Ray {
     orig: Vector3,
     dir: Vector3,
-    tm: f64
+    tm: f64,
+    // Identifies the pixel sample the ray belongs to, for hit tests that need random numbers of
+    // their own (see ConstantMedium). Zero unless set with with_key.
+    key: u64,
 }
 
 impl Ray {
-    pub fn new(origin: Vector3, direction: Vector3, time: f64) -> Self { Ray { orig: origin, dir: direction, tm: time } }
+    pub fn new(origin: Vector3, direction: Vector3, time: f64) -> Self { Ray { orig: origin, dir: direction, tm: time, key: 0 } }
+    pub fn with_key(self, key: u64) -> Self { Ray { key, ..self } }
     pub fn orig(&self) -> Vector3 { self.orig }
     pub fn dir(&self) -> Vector3 { self.dir }
     pub fn time(&self) -> f64 { self.tm }
+    pub fn key(&self) -> u64 { self.key }
     pub fn at(&self, t: f64) -> Vector3 { self.orig + (t * self.dir) }
-}
+}
\ No newline at end of file
@@ -0,0 +1,55 @@
+// Seedable xoshiro256++ generator. Every random decision in the renderer draws from one of these,
+// so a render is fully determined by its seed.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut sm = seed;
+        Rng { state: [splitmix64(&mut sm), splitmix64(&mut sm), splitmix64(&mut sm), splitmix64(&mut sm)] }
+    }
+
+    // Stream determined entirely by the given keys, for code that has no generator passed in.
+    pub fn from_keys(keys: &[u64]) -> Self {
+        let mut sm = 0;
+        for key in keys {
+            sm = splitmix64(&mut sm) ^ key;
+        }
+        Rng::new(splitmix64(&mut sm))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = (self.state[0].wrapping_add(self.state[3])).rotate_left(23).wrapping_add(self.state[0]);
+        let t = self.state[1] << 17;
+
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+
+        result
+    }
+
+    // Uniform double in [min, max).
+    pub fn random_double(&mut self, min: f64, max: f64) -> f64 {
+        let unit = (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64);
+        min + (max - min) * unit
+    }
+
+    // Uniform integer in [0, n).
+    pub fn random_index(&mut self, n: usize) -> usize {
+        (((self.next_u64() >> 32) * n as u64) >> 32) as usize
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
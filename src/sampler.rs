@@ -0,0 +1,470 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::rng::Rng;
+use crate::vector3::Vector3;
+
+// Largest double below 1.0, so that samples stay in [0, 1).
+const ONE_MINUS_EPSILON: f64 = 1.0 - f64::EPSILON / 2.0;
+
+// Source of the sample values used to render an image. Each camera sample is a point in a
+// high-dimensional unit cube: the camera takes the first dimensions for the pixel position, lens
+// and time, and integrators and materials take the following ones for each bounce. Better
+// distributed points than independent random numbers give less noise at the same sample count.
+pub trait Sampler: Send + Sync {
+    // Fresh sampler for rendering with the given number of samples per pixel and seed. The
+    // camera keeps one sampler as a prototype and makes one of these for every tile.
+    fn for_render(&self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler>;
+    // Starts the sample_index-th sample of pixel (x, y), from its first dimension.
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32);
+    fn get_1d(&mut self) -> f64;
+    fn get_2d(&mut self) -> (f64, f64);
+    // Position within the pixel, in [0, 1) on each axis.
+    fn get_pixel_2d(&mut self) -> (f64, f64) {
+        self.get_2d()
+    }
+}
+
+// Looks up a sampler by the name used on the command line and in scene files.
+pub fn sampler_by_name(name: &str) -> Result<Arc<dyn Sampler>, String> {
+    match name {
+        "independent" => Ok(Arc::new(IndependentSampler::default())),
+        "stratified" => Ok(Arc::new(StratifiedSampler::default())),
+        "halton" => Ok(Arc::new(HaltonSampler::default())),
+        "sobol" => Ok(Arc::new(SobolSampler::default())),
+        _ => Err(format!("unknown sampler \"{}\", expected one of: independent, stratified, halton, sobol", name)),
+    }
+}
+
+// Uniform random numbers, independent across dimensions and samples.
+#[derive(Clone)]
+pub struct IndependentSampler {
+    seed: u64,
+    rng: Rng,
+}
+
+impl Default for IndependentSampler {
+    fn default() -> Self { IndependentSampler { seed: 0, rng: Rng::new(0) } }
+}
+
+impl Sampler for IndependentSampler {
+    fn for_render(&self, _samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        Box::new(IndependentSampler { seed, rng: Rng::new(seed) })
+    }
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.rng = Rng::from_keys(&[self.seed, x as u64, y as u64, sample_index as u64]);
+    }
+    fn get_1d(&mut self) -> f64 {
+        self.rng.random_double(0.0, 1.0)
+    }
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.rng.random_double(0.0, 1.0), self.rng.random_double(0.0, 1.0))
+    }
+}
+
+// Jittered grid: every dimension is split into one stratum per sample (a grid of about
+// sqrt(spp) x sqrt(spp) cells in 2D), and each sample takes a random point in a different stratum.
+// The strata are shuffled independently for every dimension so that dimensions don't correlate.
+#[derive(Clone, Default)]
+pub struct StratifiedSampler {
+    state: SampleState,
+}
+
+impl Sampler for StratifiedSampler {
+    fn for_render(&self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        Box::new(StratifiedSampler { state: SampleState::new(samples_per_pixel, seed) })
+    }
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.state.start(x, y, sample_index);
+    }
+    fn get_1d(&mut self) -> f64 {
+        let (hash, mut rng) = self.state.next_dimension();
+        let count = self.state.samples_per_pixel;
+        let stratum = permutation_element(self.state.sample_index, count, hash as u32);
+        ((stratum as f64 + rng.random_double(0.0, 1.0)) / count as f64).min(ONE_MINUS_EPSILON)
+    }
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (hash, mut rng) = self.state.next_dimension();
+        let columns = (self.state.samples_per_pixel as f64).sqrt() as u32;
+        let rows = self.state.samples_per_pixel.div_ceil(columns);
+        let stratum = permutation_element(self.state.sample_index, columns * rows, hash as u32);
+        let x = ((stratum % columns) as f64 + rng.random_double(0.0, 1.0)) / columns as f64;
+        let y = ((stratum / columns) as f64 + rng.random_double(0.0, 1.0)) / rows as f64;
+        (x.min(ONE_MINUS_EPSILON), y.min(ONE_MINUS_EPSILON))
+    }
+}
+
+// Halton sequence, using the radical inverse in the n-th prime base for the n-th dimension. The
+// digits are randomly permuted per pixel and dimension (Owen scrambling), which also breaks up the
+// clumping of the first few points in large bases. Dimensions past the end of the prime table
+// fall back to independent random numbers.
+#[derive(Clone)]
+pub struct HaltonSampler {
+    state: SampleState,
+    primes: Arc<[u32]>,
+}
+
+impl Default for HaltonSampler {
+    fn default() -> Self { HaltonSampler { state: SampleState::default(), primes: first_primes(HaltonSampler::DIMENSIONS).into() } }
+}
+
+impl HaltonSampler {
+    const DIMENSIONS: usize = 256;
+
+    fn sample_dimension(&mut self) -> f64 {
+        let dimension = self.state.dimension as usize;
+        let (hash, mut rng) = self.state.next_dimension();
+        match self.primes.get(dimension) {
+            Some(&base) => owen_scrambled_radical_inverse(base, self.state.sample_index as u64, hash),
+            None => rng.random_double(0.0, 1.0),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn for_render(&self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        Box::new(HaltonSampler { state: SampleState::new(samples_per_pixel, seed), primes: self.primes.clone() })
+    }
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.state.start(x, y, sample_index);
+    }
+    fn get_1d(&mut self) -> f64 {
+        self.sample_dimension()
+    }
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.sample_dimension(), self.sample_dimension())
+    }
+}
+
+// Padded Sobol: every 1D or 2D request takes the first one or two dimensions of the Sobol
+// sequence, which are very well distributed, at a sample index shuffled per pixel and request.
+// The points are randomized with hash-based Owen scrambling, which keeps their stratification.
+// Works best with a power-of-two number of samples per pixel.
+#[derive(Clone, Default)]
+pub struct SobolSampler {
+    state: SampleState,
+}
+
+impl SobolSampler {
+    fn scrambled_index_and_seeds(&mut self) -> (u32, u32, u32) {
+        let (hash, _) = self.state.next_dimension();
+        let index = permutation_element(self.state.sample_index, self.state.samples_per_pixel, hash as u32);
+        (index, (hash >> 32) as u32, (hash >> 16) as u32 ^ 0x9e37_79b9)
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn for_render(&self, samples_per_pixel: u32, seed: u64) -> Box<dyn Sampler> {
+        Box::new(SobolSampler { state: SampleState::new(samples_per_pixel, seed) })
+    }
+    fn start_pixel_sample(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.state.start(x, y, sample_index);
+    }
+    fn get_1d(&mut self) -> f64 {
+        let (index, seed, _) = self.scrambled_index_and_seeds();
+        to_unit_float(owen_scramble(index.reverse_bits(), seed))
+    }
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (index, seed_x, seed_y) = self.scrambled_index_and_seeds();
+        let x = owen_scramble(index.reverse_bits(), seed_x);
+        let y = owen_scramble(sobol_second_dimension(index), seed_y);
+        (to_unit_float(x), to_unit_float(y))
+    }
+}
+
+// Per-render settings and the position in the current pixel sample, shared by the samplers.
+#[derive(Clone, Default)]
+struct SampleState {
+    samples_per_pixel: u32,
+    seed: u64,
+    x: u32,
+    y: u32,
+    sample_index: u32,
+    dimension: u32,
+}
+
+impl SampleState {
+    fn new(samples_per_pixel: u32, seed: u64) -> Self {
+        SampleState { samples_per_pixel: samples_per_pixel.max(1), seed, ..Default::default() }
+    }
+
+    fn start(&mut self, x: u32, y: u32, sample_index: u32) {
+        self.x = x;
+        self.y = y;
+        self.sample_index = sample_index;
+        self.dimension = 0;
+    }
+
+    // Hash of the pixel and dimension, identical for every sample of the pixel, and a random
+    // stream for this sample in this dimension.
+    fn next_dimension(&mut self) -> (u64, Rng) {
+        let (seed, x, y, dimension) = (self.seed, self.x as u64, self.y as u64, self.dimension as u64);
+        self.dimension += 1;
+        let hash = Rng::from_keys(&[seed, x, y, dimension]).next_u64();
+        (hash, Rng::from_keys(&[hash, self.sample_index as u64]))
+    }
+}
+
+// The i-th element of a random permutation of 0..n chosen by seed, without storing it
+// (Kensler, "Correlated Multi-Jittered Sampling").
+fn permutation_element(mut i: u32, n: u32, seed: u32) -> u32 {
+    if n <= 1 {
+        return 0;
+    }
+    let mut w = n - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    let p = seed;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < n {
+            break;
+        }
+    }
+    i.wrapping_add(p) % n
+}
+
+// Second dimension of the Sobol sequence, as 32 bits of fixed point.
+fn sobol_second_dimension(mut index: u32) -> u32 {
+    let mut v = 1u32 << 31;
+    let mut result = 0;
+    while index != 0 {
+        if index & 1 != 0 {
+            result ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    result
+}
+
+// Nested uniform (Owen) scrambling of a 32-bit fixed point value, using the hash-based
+// approximation of Laine and Karras as refined by Burley.
+fn owen_scramble(mut v: u32, seed: u32) -> u32 {
+    v = v.reverse_bits();
+    v ^= v.wrapping_mul(0x3d20_adea);
+    v = v.wrapping_add(seed);
+    v = v.wrapping_mul((seed >> 16) | 1);
+    v ^= v.wrapping_mul(0x0552_6c56);
+    v ^= v.wrapping_mul(0x53a2_2864);
+    v.reverse_bits()
+}
+
+fn to_unit_float(v: u32) -> f64 {
+    (v as f64 / (1u64 << 32) as f64).min(ONE_MINUS_EPSILON)
+}
+
+// Mirrors the base-b digits of index around the radix point, passing each digit through a random
+// permutation chosen by hash and the digits before it. Leading zero digits are permuted too, for
+// as many digits as fit in the 53 bits of a double.
+fn owen_scrambled_radical_inverse(base: u32, mut index: u64, hash: u64) -> f64 {
+    let digit_count = (53.0 / (base as f64).log2()) as u32;
+    let inverse_base = 1.0 / base as f64;
+    let mut inverse_base_power = 1.0;
+    let mut reversed_digits = 0u64;
+    for _ in 0..digit_count {
+        let next = index / base as u64;
+        let digit = (index - next * base as u64) as u32;
+        let digit_hash = mix_bits(hash ^ reversed_digits) as u32;
+        reversed_digits = reversed_digits * base as u64 + permutation_element(digit, base, digit_hash) as u64;
+        inverse_base_power *= inverse_base;
+        index = next;
+    }
+    (reversed_digits as f64 * inverse_base_power).min(ONE_MINUS_EPSILON)
+}
+
+fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^ (v >> 33)
+}
+
+fn first_primes(count: usize) -> Vec<u32> {
+    let mut primes: Vec<u32> = Vec::with_capacity(count);
+    let mut candidate = 2;
+    while primes.len() < count {
+        if primes.iter().take_while(|&&p| p * p <= candidate).all(|&p| candidate % p != 0) {
+            primes.push(candidate);
+        }
+        candidate += 1;
+    }
+    primes
+}
+
+// Maps a uniform square sample to the unit disk with Shirley's concentric mapping, which keeps
+// neighboring samples close together and so preserves their stratification.
+pub fn sample_concentric_disk((u1, u2): (f64, f64)) -> (f64, f64) {
+    let (ox, oy) = (2.0 * u1 - 1.0, 2.0 * u2 - 1.0);
+    if ox == 0.0 && oy == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if ox.abs() > oy.abs() {
+        (ox, (PI / 4.0) * (oy / ox))
+    } else {
+        (oy, (PI / 2.0) - (PI / 4.0) * (ox / oy))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+// Cosine-weighted direction about the z axis.
+pub fn sample_cosine_hemisphere(u: (f64, f64)) -> Vector3 {
+    let (x, y) = sample_concentric_disk(u);
+    let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+    Vector3::new(x, y, z)
+}
+
+// Uniformly distributed unit vector.
+pub fn sample_uniform_sphere((u1, u2): (f64, f64)) -> Vector3 {
+    let z = 1.0 - 2.0 * u1;
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * u2;
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_samplers() -> Vec<(&'static str, Arc<dyn Sampler>)> {
+        ["independent", "stratified", "halton", "sobol"].into_iter()
+            .map(|name| (name, sampler_by_name(name).unwrap()))
+            .collect()
+    }
+
+    // The first dimensions of every sample of pixel (x, y), alternating 1D and 2D requests.
+    fn pixel_samples(prototype: &dyn Sampler, samples_per_pixel: u32, seed: u64, x: u32, y: u32) -> Vec<Vec<f64>> {
+        let mut sampler = prototype.for_render(samples_per_pixel, seed);
+        (0..samples_per_pixel)
+            .map(|i| {
+                sampler.start_pixel_sample(x, y, i);
+                let mut values = Vec::new();
+                for _ in 0..4 {
+                    let (u, v) = sampler.get_2d();
+                    values.extend([u, v, sampler.get_1d()]);
+                }
+                values
+            })
+            .collect()
+    }
+
+    // Whether the first count values fall one into each of count equal intervals of [0, 1).
+    fn is_stratified(values: impl Iterator<Item = f64>, count: usize) -> bool {
+        let mut strata: Vec<usize> = values.take(count).map(|v| (v * count as f64) as usize).collect();
+        strata.sort();
+        strata == (0..count).collect::<Vec<_>>()
+    }
+
+    #[test]
+    fn samples_are_in_unit_interval() {
+        for (name, sampler) in all_samplers() {
+            for samples_per_pixel in [1, 7, 16] {
+                for (x, y) in [(0, 0), (3, 11), (640, 480)] {
+                    for value in pixel_samples(sampler.as_ref(), samples_per_pixel, 5, x, y).into_iter().flatten() {
+                        assert!((0.0..1.0).contains(&value), "{} gave {}", name, value);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stratified_fills_every_stratum_once() {
+        let mut sampler = StratifiedSampler::default().for_render(16, 3);
+        let (mut cells, mut strata) = (Vec::new(), Vec::new());
+        for i in 0..16 {
+            sampler.start_pixel_sample(2, 9, i);
+            let (u, v) = sampler.get_2d();
+            cells.push((u * 4.0) as usize + 4 * (v * 4.0) as usize);
+            strata.push(sampler.get_1d());
+        }
+        cells.sort();
+        assert_eq!(cells, (0..16).collect::<Vec<_>>());
+        assert!(is_stratified(strata.into_iter(), 16));
+    }
+
+    #[test]
+    fn sobol_matches_reference_points() {
+        let dimension_0 = [0.0, 0.5, 0.25, 0.75, 0.125, 0.625, 0.375, 0.875];
+        let dimension_1 = [0.0, 0.5, 0.75, 0.25, 0.625, 0.125, 0.375, 0.875];
+        for index in 0..8 {
+            assert_eq!(to_unit_float((index as u32).reverse_bits()), dimension_0[index]);
+            assert_eq!(to_unit_float(sobol_second_dimension(index as u32)), dimension_1[index]);
+        }
+    }
+
+    // Owen scrambling keeps the stratification of the unscrambled sequences: the first 2^k Sobol
+    // points and the first b^k Halton points in base b fall one into each interval of width
+    // 1 / 2^k or 1 / b^k, and the first 16 Sobol points one into each cell of a 4x4 grid.
+    #[test]
+    fn scrambled_points_keep_their_stratification() {
+        let sobol = pixel_samples(&SobolSampler::default(), 16, 1, 4, 2);
+        assert!(is_stratified(sobol.iter().map(|values| values[0]), 16));
+        assert!(is_stratified(sobol.iter().map(|values| values[1]), 16));
+        let mut cells: Vec<usize> = sobol.iter().map(|values| (values[0] * 4.0) as usize + 4 * (values[1] * 4.0) as usize).collect();
+        cells.sort();
+        assert_eq!(cells, (0..16).collect::<Vec<_>>());
+
+        let halton = pixel_samples(&HaltonSampler::default(), 27, 1, 4, 2);
+        assert!(is_stratified(halton.iter().map(|values| values[0]), 16));
+        assert!(is_stratified(halton.iter().map(|values| values[1]), 27));
+        assert!(is_stratified(halton.iter().map(|values| values[2]), 25));
+    }
+
+    #[test]
+    fn scrambling_is_deterministic_per_seed() {
+        for (name, sampler) in all_samplers() {
+            let first = pixel_samples(sampler.as_ref(), 8, 42, 3, 4);
+            assert_eq!(first, pixel_samples(sampler.as_ref(), 8, 42, 3, 4), "{}", name);
+            assert_ne!(first, pixel_samples(sampler.as_ref(), 8, 43, 3, 4), "{}", name);
+        }
+    }
+
+    // Estimates the area of the quarter disk, pi / 4, in many pixels and compares the root mean
+    // square error of each sampler against independent sampling.
+    #[test]
+    fn low_discrepancy_samplers_have_less_error() {
+        let rms_error = |prototype: &dyn Sampler| {
+            let mut sampler = prototype.for_render(64, 9);
+            let mut squared_error = 0.0;
+            for pixel in 0..256 {
+                let mut inside = 0;
+                for i in 0..64 {
+                    sampler.start_pixel_sample(pixel, 0, i);
+                    let (u, v) = sampler.get_2d();
+                    if u * u + v * v < 1.0 {
+                        inside += 1;
+                    }
+                }
+                squared_error += (inside as f64 / 64.0 - PI / 4.0).powi(2);
+            }
+            (squared_error / 256.0).sqrt()
+        };
+        let independent = rms_error(&IndependentSampler::default());
+        for (name, sampler) in all_samplers().into_iter().skip(1) {
+            let error = rms_error(sampler.as_ref());
+            assert!(error < 0.5 * independent, "{} has an error of {} against {} for independent", name, error, independent);
+        }
+    }
+}
@@ -0,0 +1,873 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::aabb::Aabb;
+use crate::background::{Background, EnvironmentBackground, GradientBackground, SolidBackground};
+use crate::bvh::BvhNode;
+use crate::camera::Camera;
+use crate::constant_medium::ConstantMedium;
+use crate::hittable::{Hittable, HittableList};
+use crate::integrator::{
+    AlbedoIntegrator, AmbientOcclusionIntegrator, DepthIntegrator, Integrator, MisIntegrator, NormalIntegrator,
+    PathIntegrator, SpectralIntegrator, UvIntegrator,
+};
+use crate::material::{Conductor, Dielectric, DiffuseLight, Dispersion, Isotropic, Lambertian, Material, Metal, RoughDielectric};
+use crate::moving_sphere::MovingSphere;
+use crate::obj::load_obj;
+use crate::principled::Principled;
+use crate::quad::{make_box, Quad};
+use crate::rng::Rng;
+use crate::sampler::sampler_by_name;
+use crate::scenes::random_spheres::random_spheres;
+use crate::sphere::Sphere;
+use crate::texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture};
+use crate::transform::{Matrix4, Transform};
+use crate::tonemap::TransferFunction;
+use crate::triangle::Triangle;
+use crate::vector3::Vector3;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io { path: PathBuf, source: io::Error },
+    Parse { path: PathBuf, message: String },
+    Invalid { path: PathBuf, location: String, message: String },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            SceneError::Parse { path, message } => write!(f, "{}: {}", path.display(), message),
+            SceneError::Invalid { path, location, message } => write!(f, "{}: {}: {}", path.display(), location, message),
+        }
+    }
+}
+
+impl Error for SceneError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SceneError::Io { source, .. } => Some(source),
+            SceneError::Parse { .. } | SceneError::Invalid { .. } => None,
+        }
+    }
+}
+
+// Top level of a scene file. Textures and materials are named so that objects, and other
+// textures, can refer to them.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDescription {
+    pub camera: CameraDescription,
+    // Seed for noise textures, random spheres and sampling, unless load_scene is given one.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub background: Option<BackgroundDescription>,
+    #[serde(default)]
+    pub integrator: Option<IntegratorDescription>,
+    // One of the names accepted by sampler::sampler_by_name.
+    #[serde(default)]
+    pub sampler: Option<String>,
+    #[serde(default)]
+    pub textures: BTreeMap<String, TextureDescription>,
+    #[serde(default)]
+    pub materials: BTreeMap<String, MaterialDescription>,
+    #[serde(default)]
+    pub objects: Vec<ObjectDescription>,
+    // Shapes sampled for direct lighting. Their materials are ignored and may be left out.
+    #[serde(default)]
+    pub lights: Vec<ObjectDescription>,
+    // Wrap the objects in a bounding volume hierarchy.
+    #[serde(default)]
+    pub bvh: bool,
+}
+
+// The arguments of Camera::new.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraDescription {
+    pub aspect_ratio: f64,
+    pub image_width: u32,
+    pub samples_per_pixel: u32,
+    pub max_depth: u32,
+    pub vfov: f64,
+    pub look_from: [f64; 3],
+    pub look_at: [f64; 3],
+    #[serde(default = "default_v_up")]
+    pub v_up: [f64; 3],
+    #[serde(default)]
+    pub defocus_angle: f64,
+    #[serde(default = "default_focus_dist")]
+    pub focus_dist: f64,
+    #[serde(default)]
+    pub time0: f64,
+    #[serde(default)]
+    pub time1: f64,
+    // Adaptive sampling, see Camera::noise_threshold. samples_per_pixel is then the maximum.
+    #[serde(default)]
+    pub noise_threshold: f64,
+    #[serde(default)]
+    pub min_samples_per_pixel: Option<u32>,
+    // Post-process for 8-bit output, see tonemap::PostProcess. tone_map takes the names
+    // accepted by ToneMap::from_str, and gamma replaces the sRGB curve with a power curve.
+    #[serde(default)]
+    pub exposure: f64,
+    #[serde(default)]
+    pub tone_map: Option<String>,
+    #[serde(default)]
+    pub gamma: Option<f64>,
+    #[serde(default)]
+    pub dither: bool,
+}
+
+// Either an RGB color or the name of a texture.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ColorOrTexture {
+    Color([f64; 3]),
+    Texture(String),
+}
+
+// A constant index of refraction, the name of a dispersive glass (bk7, fused_silica or
+// diamond), or the coefficients of a dispersion formula.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum RefractionIndexDescription {
+    Constant(f64),
+    Named(String),
+    Cauchy { a: f64, b: f64 },
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+// Either a number or the name of a texture, for scalar parameters.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ValueOrTexture {
+    Value(f64),
+    Texture(String),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum TextureDescription {
+    Solid { color: [f64; 3] },
+    Checker { even: ColorOrTexture, odd: ColorOrTexture },
+    // The path is relative to the scene file.
+    Image { path: PathBuf },
+    Noise { scale: f64 },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum MaterialDescription {
+    Lambertian { albedo: ColorOrTexture },
+    Metal { albedo: [f64; 3], fuzz: f64 },
+    // Either a named metal (gold, silver, copper, aluminium) or explicit eta and k per channel.
+    Conductor {
+        metal: Option<String>,
+        eta: Option<[f64; 3]>,
+        k: Option<[f64; 3]>,
+        #[serde(default)]
+        roughness: f64,
+    },
+    // Absorption per unit length, or the transmittance after a distance (1 by default) inside.
+    Dielectric {
+        refraction_index: RefractionIndexDescription,
+        absorption: Option<[f64; 3]>,
+        transmittance: Option<[f64; 3]>,
+        distance: Option<f64>,
+    },
+    RoughDielectric { refraction_index: f64, roughness: ValueOrTexture },
+    DiffuseLight { emit: ColorOrTexture },
+    Isotropic { albedo: ColorOrTexture },
+    // Parameters left out keep the defaults of Principled::new.
+    Principled {
+        base_color: ColorOrTexture,
+        metallic: Option<ValueOrTexture>,
+        roughness: Option<ValueOrTexture>,
+        specular: Option<ValueOrTexture>,
+        specular_tint: Option<ValueOrTexture>,
+        sheen: Option<ValueOrTexture>,
+        sheen_tint: Option<ValueOrTexture>,
+        clearcoat: Option<ValueOrTexture>,
+        clearcoat_gloss: Option<ValueOrTexture>,
+        transmission: Option<ValueOrTexture>,
+        ior: Option<ValueOrTexture>,
+        anisotropic: Option<ValueOrTexture>,
+        anisotropic_rotation: Option<ValueOrTexture>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ObjectDescription {
+    Sphere { center: [f64; 3], radius: f64, material: Option<String> },
+    MovingSphere {
+        center0: [f64; 3],
+        center1: [f64; 3],
+        #[serde(default)]
+        time0: f64,
+        #[serde(default = "default_time1")]
+        time1: f64,
+        radius: f64,
+        material: Option<String>,
+    },
+    Quad { q: [f64; 3], u: [f64; 3], v: [f64; 3], material: Option<String> },
+    Box { a: [f64; 3], b: [f64; 3], material: Option<String> },
+    Triangle { a: [f64; 3], b: [f64; 3], c: [f64; 3], material: Option<String> },
+    // Wavefront OBJ file, relative to the scene file, using the materials from its MTL files.
+    Obj { path: PathBuf },
+    ConstantMedium { boundary: Box<ObjectDescription>, density: f64, albedo: ColorOrTexture },
+    // The grid of small random spheres from the cover and bouncing spheres scenes, see
+    // scenes::random_spheres. Uses the scene's seed unless given its own, so that the same seed
+    // reproduces the built-in scenes.
+    RandomSpheres {
+        seed: Option<u64>,
+        #[serde(default)]
+        bounce_height: f64,
+    },
+    Group { objects: Vec<ObjectDescription> },
+    // The steps are applied to the object in order.
+    Transform { object: Box<ObjectDescription>, steps: Vec<TransformStep> },
+}
+
+// One of translate, scale or rotate_x/y/z (in degrees), e.g. { "rotate_y": 15 }.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TransformStep {
+    translate: Option<[f64; 3]>,
+    scale: Option<[f64; 3]>,
+    rotate_x: Option<f64>,
+    rotate_y: Option<f64>,
+    rotate_z: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BackgroundDescription {
+    Solid { color: [f64; 3] },
+    Gradient { bottom: [f64; 3], top: [f64; 3] },
+    Environment { texture: ColorOrTexture },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum IntegratorDescription {
+    Path,
+    Mis {
+        #[serde(default = "default_roulette_start_depth")]
+        roulette_start_depth: u32,
+    },
+    Spectral {
+        #[serde(default = "default_roulette_start_depth")]
+        roulette_start_depth: u32,
+    },
+    Normal,
+    Uv,
+    Depth { max_distance: f64 },
+    Albedo,
+    AmbientOcclusion { samples: u32, distance: f64 },
+}
+
+fn default_v_up() -> [f64; 3] { [0.0, 1.0, 0.0] }
+fn default_focus_dist() -> f64 { 10.0 }
+fn default_time1() -> f64 { 1.0 }
+fn default_roulette_start_depth() -> u32 { MisIntegrator::default().roulette_start_depth }
+
+// Loads a scene from a JSON, TOML or YAML file, picked by the file extension. The seed, or else
+// the file's own (0 if it has none), is used for noise textures and becomes the camera's seed,
+// as with scenes::get_scene.
+pub fn load_scene(path: impl AsRef<Path>, seed: Option<u64>) -> Result<(HittableList, Camera), SceneError> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path).map_err(|source| SceneError::Io { path: path.to_path_buf(), source })?;
+    let parse_error = |message: String| SceneError::Parse { path: path.to_path_buf(), message };
+
+    let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase());
+    let description: SceneDescription = match extension.as_deref() {
+        Some("json") => serde_json::from_str(&source).map_err(|e| parse_error(e.to_string()))?,
+        Some("toml") => toml::from_str(&source).map_err(|e| parse_error(e.to_string()))?,
+        Some("yaml") | Some("yml") => serde_yaml::from_str(&source).map_err(|e| parse_error(e.to_string()))?,
+        _ => return Err(parse_error("unknown scene format, expected a .json, .toml or .yaml file".to_string())),
+    };
+
+    build_scene(&description, path, seed)
+}
+
+// Builds the world and camera for an already parsed description. Relative paths inside it are
+// resolved against the directory of scene_path, which also prefixes error messages.
+pub fn build_scene(description: &SceneDescription, scene_path: &Path, seed: Option<u64>) -> Result<(HittableList, Camera), SceneError> {
+    let seed = seed.or(description.seed).unwrap_or(0);
+    let mut builder = SceneBuilder {
+        description,
+        scene_path,
+        seed,
+        rng: Rng::new(seed),
+        textures: HashMap::new(),
+        textures_in_progress: HashSet::new(),
+        materials: HashMap::new(),
+    };
+
+    for name in description.materials.keys() {
+        builder.material(name, "materials")?;
+    }
+
+    let mut world = HittableList::new();
+    for (i, object) in description.objects.iter().enumerate() {
+        world.push_arc(builder.object(object, &format!("objects[{}]", i))?);
+    }
+
+    let c = &description.camera;
+    let mut camera = Camera::new(
+        c.aspect_ratio, c.image_width, c.samples_per_pixel, c.max_depth, c.vfov,
+        vector(c.look_from), vector(c.look_at), vector(c.v_up),
+        c.defocus_angle, c.focus_dist, c.time0, c.time1,
+    );
+    camera.seed = seed;
+    camera.noise_threshold = c.noise_threshold;
+    if let Some(min_samples) = c.min_samples_per_pixel {
+        camera.min_samples_per_pixel = min_samples;
+    }
+    camera.post_process.exposure = c.exposure;
+    if let Some(tone_map) = &c.tone_map {
+        camera.post_process.tone_map = tone_map.parse().map_err(|message| builder.error("camera.tone_map", message))?;
+    }
+    if let Some(gamma) = c.gamma {
+        if gamma <= 0.0 {
+            return Err(builder.error("camera.gamma", "gamma must be positive".to_string()));
+        }
+        camera.post_process.transfer = TransferFunction::Gamma(gamma);
+    }
+    camera.post_process.dither = c.dither;
+
+    if let Some(background) = &description.background {
+        camera.background = builder.background(background)?;
+    }
+    if let Some(sampler) = &description.sampler {
+        camera.sampler = sampler_by_name(sampler).map_err(|message| builder.error("sampler", message))?;
+    }
+    if let Some(integrator) = &description.integrator {
+        camera.integrator = integrator_from(integrator);
+    }
+    if !description.lights.is_empty() {
+        let mut lights = HittableList::new();
+        for (i, light) in description.lights.iter().enumerate() {
+            let location = format!("lights[{}]", i);
+            builder.check_light(light, &location)?;
+            lights.push_arc(builder.object(light, &location)?);
+        }
+        camera.lights = Some(Arc::new(lights));
+    }
+
+    if description.bvh && !world.is_empty() {
+        let bvh = builder.bvh(&world, "objects")?;
+        world = HittableList::new();
+        world.push(bvh);
+    }
+
+    Ok((world, camera))
+}
+
+struct SceneBuilder<'a> {
+    description: &'a SceneDescription,
+    scene_path: &'a Path,
+    seed: u64,
+    rng: Rng,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    textures_in_progress: HashSet<String>,
+    materials: HashMap<String, Arc<dyn Material>>,
+}
+
+impl SceneBuilder<'_> {
+    fn error(&self, location: &str, message: String) -> SceneError {
+        SceneError::Invalid { path: self.scene_path.to_path_buf(), location: location.to_string(), message }
+    }
+
+    fn relative_path(&self, path: &Path) -> PathBuf {
+        match self.scene_path.parent() {
+            Some(directory) => directory.join(path),
+            None => path.to_path_buf(),
+        }
+    }
+
+    // Textures are built the first time they are referenced, so they may be listed in any order.
+    fn texture(&mut self, name: &str, location: &str) -> Result<Arc<dyn Texture>, SceneError> {
+        if let Some(texture) = self.textures.get(name) {
+            return Ok(texture.clone());
+        }
+        let Some(description) = self.description.textures.get(name) else {
+            return Err(self.error(location, format!("unknown texture \"{}\"", name)));
+        };
+        if !self.textures_in_progress.insert(name.to_string()) {
+            return Err(self.error(location, format!("texture \"{}\" refers to itself", name)));
+        }
+
+        let location = format!("textures.{}", name);
+        let texture: Arc<dyn Texture> = match description {
+            TextureDescription::Solid { color } => Arc::new(SolidColor::new(vector(*color))),
+            TextureDescription::Checker { even, odd } => {
+                let even = self.color_or_texture(even, &format!("{}.even", location))?;
+                let odd = self.color_or_texture(odd, &format!("{}.odd", location))?;
+                Arc::new(CheckerTexture::new(even, odd))
+            }
+            TextureDescription::Image { path } => {
+                let image_path = self.relative_path(path);
+                let texture = ImageTexture::from_path(&image_path)
+                    .map_err(|e| self.error(&format!("{}.path", location), format!("{}: {}", image_path.display(), e)))?;
+                Arc::new(texture)
+            }
+            TextureDescription::Noise { scale } => Arc::new(NoiseTexture::new(*scale, &mut self.rng)),
+        };
+
+        self.textures_in_progress.remove(name);
+        self.textures.insert(name.to_string(), texture.clone());
+        Ok(texture)
+    }
+
+    fn color_or_texture(&mut self, value: &ColorOrTexture, location: &str) -> Result<Arc<dyn Texture>, SceneError> {
+        match value {
+            ColorOrTexture::Color(color) => Ok(Arc::new(SolidColor::new(vector(*color)))),
+            ColorOrTexture::Texture(name) => self.texture(name, location),
+        }
+    }
+
+    fn value_or_texture(&mut self, value: &ValueOrTexture, location: &str) -> Result<Arc<dyn Texture>, SceneError> {
+        match value {
+            ValueOrTexture::Value(value) => Ok(Arc::new(SolidColor::from_rgb(*value, *value, *value))),
+            ValueOrTexture::Texture(name) => self.texture(name, location),
+        }
+    }
+
+    fn material(&mut self, name: &str, location: &str) -> Result<Arc<dyn Material>, SceneError> {
+        if let Some(material) = self.materials.get(name) {
+            return Ok(material.clone());
+        }
+        let Some(description) = self.description.materials.get(name) else {
+            return Err(self.error(location, format!("unknown material \"{}\"", name)));
+        };
+
+        let location = format!("materials.{}", name);
+        let material: Arc<dyn Material> = match description {
+            MaterialDescription::Lambertian { albedo } => {
+                Arc::new(Lambertian::new_from_texture(self.color_or_texture(albedo, &format!("{}.albedo", location))?))
+            }
+            MaterialDescription::Metal { albedo, fuzz } => Arc::new(Metal::new(vector(*albedo), *fuzz)),
+            MaterialDescription::Conductor { metal, eta, k, roughness } => match (metal, eta, k) {
+                (Some(metal), None, None) => match Conductor::by_name(metal, *roughness) {
+                    Some(conductor) => Arc::new(conductor),
+                    None => return Err(self.error(&format!("{}.metal", location), format!("unknown metal \"{}\"", metal))),
+                },
+                (None, Some(eta), Some(k)) => Arc::new(Conductor::new(vector(*eta), vector(*k), *roughness)),
+                _ => return Err(self.error(&location, "expected either metal, or both eta and k".to_string())),
+            },
+            MaterialDescription::Dielectric { refraction_index, absorption, transmittance, distance } => {
+                if distance.is_some() && transmittance.is_none() {
+                    return Err(self.error(&format!("{}.distance", location), "only applies together with transmittance".to_string()));
+                }
+                let absorption = match (absorption, transmittance) {
+                    (None, None) => Vector3::new(0.0, 0.0, 0.0),
+                    (Some(absorption), None) => vector(*absorption),
+                    (None, Some(transmittance)) => {
+                        let distance = distance.unwrap_or(1.0);
+                        if distance <= 0.0 {
+                            return Err(self.error(&format!("{}.distance", location), "must be positive".to_string()));
+                        }
+                        Dielectric::absorption_from_transmittance(vector(*transmittance), distance)
+                    }
+                    (Some(_), Some(_)) => {
+                        return Err(self.error(&location, "expected either absorption or transmittance, not both".to_string()))
+                    }
+                };
+                match refraction_index {
+                    RefractionIndexDescription::Constant(refraction_index) => {
+                        Arc::new(Dielectric::new_with_absorption(*refraction_index, absorption))
+                    }
+                    RefractionIndexDescription::Named(glass) => match Dispersion::by_name(glass) {
+                        Some(dispersion) => Arc::new(Dielectric::new_dispersive(dispersion, absorption)),
+                        None => {
+                            let location = format!("{}.refraction_index", location);
+                            return Err(self.error(&location, format!("unknown glass \"{}\"", glass)));
+                        }
+                    },
+                    RefractionIndexDescription::Cauchy { a, b } => {
+                        Arc::new(Dielectric::new_dispersive(Dispersion::Cauchy { a: *a, b: *b }, absorption))
+                    }
+                    RefractionIndexDescription::Sellmeier { b, c } => {
+                        Arc::new(Dielectric::new_dispersive(Dispersion::Sellmeier { b: *b, c: *c }, absorption))
+                    }
+                }
+            }
+            MaterialDescription::RoughDielectric { refraction_index, roughness } => Arc::new(RoughDielectric::new_from_texture(
+                *refraction_index,
+                self.value_or_texture(roughness, &format!("{}.roughness", location))?,
+            )),
+            MaterialDescription::DiffuseLight { emit } => {
+                Arc::new(DiffuseLight::new_from_texture(self.color_or_texture(emit, &format!("{}.emit", location))?))
+            }
+            MaterialDescription::Isotropic { albedo } => {
+                Arc::new(Isotropic::new_from_texture(self.color_or_texture(albedo, &format!("{}.albedo", location))?))
+            }
+            MaterialDescription::Principled {
+                base_color,
+                metallic,
+                roughness,
+                specular,
+                specular_tint,
+                sheen,
+                sheen_tint,
+                clearcoat,
+                clearcoat_gloss,
+                transmission,
+                ior,
+                anisotropic,
+                anisotropic_rotation,
+            } => {
+                let mut principled =
+                    Principled::new_from_texture(self.color_or_texture(base_color, &format!("{}.base_color", location))?);
+                let parameters = [
+                    (metallic, &mut principled.metallic, "metallic"),
+                    (roughness, &mut principled.roughness, "roughness"),
+                    (specular, &mut principled.specular, "specular"),
+                    (specular_tint, &mut principled.specular_tint, "specular_tint"),
+                    (sheen, &mut principled.sheen, "sheen"),
+                    (sheen_tint, &mut principled.sheen_tint, "sheen_tint"),
+                    (clearcoat, &mut principled.clearcoat, "clearcoat"),
+                    (clearcoat_gloss, &mut principled.clearcoat_gloss, "clearcoat_gloss"),
+                    (transmission, &mut principled.transmission, "transmission"),
+                    (ior, &mut principled.ior, "ior"),
+                    (anisotropic, &mut principled.anisotropic, "anisotropic"),
+                    (anisotropic_rotation, &mut principled.anisotropic_rotation, "anisotropic_rotation"),
+                ];
+                for (value, texture, field) in parameters {
+                    if let Some(value) = value {
+                        *texture = self.value_or_texture(value, &format!("{}.{}", location, field))?;
+                    }
+                }
+                Arc::new(principled)
+            }
+        };
+
+        self.materials.insert(name.to_string(), material.clone());
+        Ok(material)
+    }
+
+    fn object_material(&mut self, name: &Option<String>, location: &str) -> Result<Option<Arc<dyn Material>>, SceneError> {
+        match name {
+            Some(name) => Ok(Some(self.material(name, &format!("{}.material", location))?)),
+            None => Ok(None),
+        }
+    }
+
+    fn object(&mut self, description: &ObjectDescription, location: &str) -> Result<Arc<dyn Hittable>, SceneError> {
+        let object: Arc<dyn Hittable> = match description {
+            ObjectDescription::Sphere { center, radius, material } => {
+                Arc::new(Sphere::new(vector(*center), *radius, self.object_material(material, location)?))
+            }
+            ObjectDescription::MovingSphere { center0, center1, time0, time1, radius, material } => {
+                let material = self.object_material(material, location)?;
+                Arc::new(MovingSphere::new(vector(*center0), vector(*center1), *time0, *time1, *radius, material))
+            }
+            ObjectDescription::Quad { q, u, v, material } => {
+                Arc::new(Quad::new(vector(*q), vector(*u), vector(*v), self.object_material(material, location)?))
+            }
+            ObjectDescription::Box { a, b, material } => {
+                Arc::new(make_box(vector(*a), vector(*b), self.object_material(material, location)?))
+            }
+            ObjectDescription::Triangle { a, b, c, material } => {
+                Arc::new(Triangle::new(vector(*a), vector(*b), vector(*c), self.object_material(material, location)?))
+            }
+            ObjectDescription::Obj { path } => {
+                let obj_path = self.relative_path(path);
+                let meshes = load_obj(&obj_path).map_err(|e| self.error(&format!("{}.path", location), e.to_string()))?;
+                Arc::new(meshes)
+            }
+            ObjectDescription::ConstantMedium { boundary, density, albedo } => {
+                let boundary = self.object(boundary, &format!("{}.boundary", location))?;
+                let albedo = self.color_or_texture(albedo, &format!("{}.albedo", location))?;
+                Arc::new(ConstantMedium::new_from_texture(boundary, *density, albedo))
+            }
+            ObjectDescription::RandomSpheres { seed, bounce_height } => {
+                let mut rng = Rng::new(seed.unwrap_or(self.seed));
+                let spheres = random_spheres(&mut rng, *bounce_height);
+                Arc::new(self.bvh(&spheres, location)?)
+            }
+            ObjectDescription::Group { objects } => {
+                let mut group = HittableList::new();
+                for (i, object) in objects.iter().enumerate() {
+                    group.push_arc(self.object(object, &format!("{}.objects[{}]", location, i))?);
+                }
+                Arc::new(group)
+            }
+            ObjectDescription::Transform { object, steps } => {
+                let object = self.object(object, &format!("{}.object", location))?;
+                let mut matrix = Matrix4::identity();
+                for (i, step) in steps.iter().enumerate() {
+                    let step_location = format!("{}.steps[{}]", location, i);
+                    matrix = step_matrix(step).map_err(|message| self.error(&step_location, message))? * matrix;
+                }
+                let transform = Transform::new(object, matrix)
+                    .ok_or_else(|| self.error(&format!("{}.steps", location), "transform is not invertible".to_string()))?;
+                Arc::new(transform)
+            }
+        };
+        Ok(object)
+    }
+
+    // BvhNode::new gives no reason when it fails, so objects without a bounding box, such as
+    // empty groups, are located here. location is that of the list the objects came from.
+    fn bvh(&self, objects: &HittableList, location: &str) -> Result<BvhNode, SceneError> {
+        let camera = &self.description.camera;
+        for (i, object) in objects.objects().iter().enumerate() {
+            let mut bbox = Aabb::default();
+            if !object.bounding_box(camera.time0, camera.time1, &mut bbox) {
+                let message = "has no bounding box for the bounding volume hierarchy, e.g. because it is an empty group";
+                return Err(self.error(&format!("{}[{}]", location, i), message.to_string()));
+            }
+        }
+        BvhNode::new(objects, camera.time0, camera.time1)
+            .ok_or_else(|| self.error(location, "no objects to build a bounding volume hierarchy from".to_string()))
+    }
+
+    // Only these shapes aim their light samples at themselves; any other object would be
+    // sampled uniformly over all directions, wasting most of the light's samples.
+    fn check_light(&self, description: &ObjectDescription, location: &str) -> Result<(), SceneError> {
+        match description {
+            ObjectDescription::Sphere { .. }
+            | ObjectDescription::Quad { .. }
+            | ObjectDescription::Box { .. }
+            | ObjectDescription::Triangle { .. } => Ok(()),
+            ObjectDescription::Group { objects } => {
+                for (i, object) in objects.iter().enumerate() {
+                    self.check_light(object, &format!("{}.objects[{}]", location, i))?;
+                }
+                Ok(())
+            }
+            ObjectDescription::Transform { object, .. } => self.check_light(object, &format!("{}.object", location)),
+            ObjectDescription::ConstantMedium { boundary, .. } => self.check_light(boundary, &format!("{}.boundary", location)),
+            _ => Err(self.error(
+                location,
+                "lights must be spheres, quads, boxes or triangles, or groups, transforms or constant media of them, the shapes that can be sampled".to_string(),
+            )),
+        }
+    }
+
+    fn background(&mut self, description: &BackgroundDescription) -> Result<Arc<dyn Background>, SceneError> {
+        let background: Arc<dyn Background> = match description {
+            BackgroundDescription::Solid { color } => Arc::new(SolidBackground::new(vector(*color))),
+            BackgroundDescription::Gradient { bottom, top } => Arc::new(GradientBackground::new(vector(*bottom), vector(*top))),
+            BackgroundDescription::Environment { texture } => {
+                Arc::new(EnvironmentBackground::new(self.color_or_texture(texture, "background.texture")?))
+            }
+        };
+        Ok(background)
+    }
+}
+
+fn step_matrix(step: &TransformStep) -> Result<Matrix4, String> {
+    let mut matrices = Vec::new();
+    if let Some(offset) = step.translate { matrices.push(Matrix4::translation(vector(offset))); }
+    if let Some(factors) = step.scale { matrices.push(Matrix4::scaling(vector(factors))); }
+    if let Some(degrees) = step.rotate_x { matrices.push(Matrix4::rotation_x(degrees)); }
+    if let Some(degrees) = step.rotate_y { matrices.push(Matrix4::rotation_y(degrees)); }
+    if let Some(degrees) = step.rotate_z { matrices.push(Matrix4::rotation_z(degrees)); }
+
+    match matrices[..] {
+        [matrix] => Ok(matrix),
+        _ => Err("expected exactly one of translate, scale, rotate_x, rotate_y or rotate_z".to_string()),
+    }
+}
+
+fn integrator_from(description: &IntegratorDescription) -> Arc<dyn Integrator> {
+    match *description {
+        IntegratorDescription::Path => Arc::new(PathIntegrator),
+        IntegratorDescription::Mis { roulette_start_depth } => Arc::new(MisIntegrator { roulette_start_depth }),
+        IntegratorDescription::Spectral { roulette_start_depth } => Arc::new(SpectralIntegrator { roulette_start_depth }),
+        IntegratorDescription::Normal => Arc::new(NormalIntegrator),
+        IntegratorDescription::Uv => Arc::new(UvIntegrator),
+        IntegratorDescription::Depth { max_distance } => Arc::new(DepthIntegrator::new(max_distance)),
+        IntegratorDescription::Albedo => Arc::new(AlbedoIntegrator),
+        IntegratorDescription::AmbientOcclusion { samples, distance } => Arc::new(AmbientOcclusionIntegrator::new(samples, distance)),
+    }
+}
+
+fn vector(v: [f64; 3]) -> Vector3 {
+    Vector3::new(v[0], v[1], v[2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use crate::tonemap::{PostProcess, ToneMap};
+
+    const CAMERA: &str = r#""camera": { "aspect_ratio": 1.0, "image_width": 10, "samples_per_pixel": 1, "max_depth": 5,
+        "vfov": 40.0, "look_from": [0.0, 0.0, 1.0], "look_at": [0.0, 0.0, 0.0] }"#;
+
+    fn build_error(json: &str) -> (String, String) {
+        let description: SceneDescription = serde_json::from_str(json).unwrap();
+        match build_scene(&description, Path::new("test.json"), None) {
+            Err(SceneError::Invalid { location, message, .. }) => (location, message),
+            Err(e) => panic!("expected an invalid scene error, got {}", e),
+            Ok(_) => panic!("expected an invalid scene error"),
+        }
+    }
+
+    fn parse_error(name: &str, contents: &str) -> String {
+        let directory = TempDir::new();
+        let path = directory.write(name, contents);
+        match load_scene(&path, None) {
+            Err(SceneError::Parse { path: error_path, message }) => {
+                assert_eq!(error_path, path);
+                message
+            }
+            Err(e) => panic!("expected a parse error, got {}", e),
+            Ok(_) => panic!("expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn loads_valid_scene() {
+        let directory = TempDir::new();
+        let path = directory.write("valid.json", &format!(r#"{{ {}, "materials": {{ "red": {{ "type": "lambertian", "albedo": [1.0, 0.0, 0.0] }} }},
+            "objects": [{{ "type": "sphere", "center": [0.0, 0.0, 0.0], "radius": 0.5, "material": "red" }}] }}"#, CAMERA));
+        let (world, camera) = load_scene(&path, None).unwrap();
+        assert_eq!(world.len(), 1);
+        assert_eq!(camera.image_width, 10);
+    }
+
+    #[test]
+    fn loads_every_example_scene() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes");
+        for entry in fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            if let Err(e) = load_scene(&path, None) {
+                panic!("{}", e);
+            }
+        }
+    }
+
+    #[test]
+    fn reports_line_of_json_syntax_error() {
+        let message = parse_error("syntax.json", &format!("{{ {},\n  \"objects\": [\n    {{ \"type\": \"sphere\" \"radius\": 1.0 }}\n  ]\n}}", CAMERA));
+        assert!(message.contains("line 4"), "{}", message);
+    }
+
+    #[test]
+    fn reports_line_of_toml_type_error() {
+        let message = parse_error("type.toml", "[camera]\naspect_ratio = \"wide\"\n");
+        assert!(message.contains("line 2"), "{}", message);
+    }
+
+    #[test]
+    fn reports_line_of_unknown_yaml_field() {
+        let message = parse_error("field.yaml", "camera:\n  aspect_ratio: 1.0\n  width: 10\n");
+        assert!(message.contains("unknown field `width`") && message.contains("line 3"), "{}", message);
+    }
+
+    #[test]
+    fn rejects_unknown_extension() {
+        let message = parse_error("scene.txt", "");
+        assert!(message.contains("unknown scene format"), "{}", message);
+    }
+
+    #[test]
+    fn locates_unknown_material() {
+        let (location, message) = build_error(&format!(r#"{{ {}, "objects": [
+            {{ "type": "sphere", "center": [0.0, 0.0, 0.0], "radius": 0.5 }},
+            {{ "type": "transform", "object": {{ "type": "sphere", "center": [0.0, 0.0, 0.0], "radius": 0.5, "material": "gold" }},
+              "steps": [] }}] }}"#, CAMERA));
+        assert_eq!(location, "objects[1].object.material");
+        assert!(message.contains("unknown material \"gold\""), "{}", message);
+    }
+
+    #[test]
+    fn given_seed_overrides_scene_seed() {
+        let description: SceneDescription = serde_json::from_str(&format!(r#"{{ {}, "seed": 5 }}"#, CAMERA)).unwrap();
+        assert_eq!(build_scene(&description, Path::new("test.json"), None).unwrap().1.seed, 5);
+        assert_eq!(build_scene(&description, Path::new("test.json"), Some(7)).unwrap().1.seed, 7);
+    }
+
+    #[test]
+    fn reads_post_process_from_camera() {
+        let description: SceneDescription = serde_json::from_str(r#"{ "camera": { "aspect_ratio": 1.0, "image_width": 10,
+            "samples_per_pixel": 1, "max_depth": 5, "vfov": 40.0, "look_from": [0.0, 0.0, 1.0], "look_at": [0.0, 0.0, 0.0],
+            "exposure": -1.5, "tone_map": "extended-reinhard:4", "gamma": 2.2, "dither": true } }"#).unwrap();
+        let (_, camera) = build_scene(&description, Path::new("test.json"), None).unwrap();
+        assert_eq!(camera.post_process, PostProcess {
+            exposure: -1.5,
+            tone_map: ToneMap::ExtendedReinhard { white_point: 4.0 },
+            transfer: TransferFunction::Gamma(2.2),
+            dither: true,
+        });
+    }
+
+    #[test]
+    fn locates_unknown_tone_map() {
+        let (location, message) = build_error(r#"{ "camera": { "aspect_ratio": 1.0, "image_width": 10, "samples_per_pixel": 1,
+            "max_depth": 5, "vfov": 40.0, "look_from": [0.0, 0.0, 1.0], "look_at": [0.0, 0.0, 0.0], "tone_map": "filmic" } }"#);
+        assert_eq!(location, "camera.tone_map");
+        assert!(message.contains("unknown tone map \"filmic\""), "{}", message);
+    }
+
+    #[test]
+    fn rejects_distance_without_transmittance() {
+        let (location, message) = build_error(&format!(r#"{{ {}, "materials": {{
+            "tinted": {{ "type": "dielectric", "refraction_index": 1.5, "absorption": [0.1, 0.2, 0.3], "distance": 2.0 }} }} }}"#, CAMERA));
+        assert_eq!(location, "materials.tinted.distance");
+        assert!(message.contains("transmittance"), "{}", message);
+    }
+
+    #[test]
+    fn locates_texture_cycles() {
+        let (location, message) = build_error(&format!(r#"{{ {},
+            "textures": {{ "a": {{ "type": "checker", "even": "b", "odd": [0.0, 0.0, 0.0] }},
+                           "b": {{ "type": "checker", "even": [1.0, 1.0, 1.0], "odd": "a" }} }},
+            "materials": {{ "floor": {{ "type": "lambertian", "albedo": "a" }} }} }}"#, CAMERA));
+        assert_eq!(location, "textures.b.odd");
+        assert!(message.contains("texture \"a\" refers to itself"), "{}", message);
+    }
+
+    #[test]
+    fn locates_objects_without_bounding_box_in_bvh() {
+        let (location, message) = build_error(&format!(r#"{{ {}, "bvh": true, "objects": [
+            {{ "type": "sphere", "center": [0.0, 0.0, 0.0], "radius": 0.5 }},
+            {{ "type": "group", "objects": [] }}] }}"#, CAMERA));
+        assert_eq!(location, "objects[1]");
+        assert!(message.contains("no bounding box"), "{}", message);
+    }
+
+    #[test]
+    fn rejects_lights_that_cannot_be_sampled() {
+        let (location, message) = build_error(&format!(r#"{{ {}, "lights": [
+            {{ "type": "group", "objects": [
+                {{ "type": "triangle", "a": [0.0, 1.0, 0.0], "b": [1.0, 1.0, 0.0], "c": [0.0, 1.0, 1.0] }},
+                {{ "type": "transform", "object": {{ "type": "moving_sphere", "center0": [0.0, 0.0, 0.0],
+                  "center1": [0.0, 1.0, 0.0], "radius": 0.5 }}, "steps": [] }}] }}] }}"#, CAMERA));
+        assert_eq!(location, "lights[0].objects[1].object");
+        assert!(message.contains("lights must be"), "{}", message);
+    }
+
+    #[test]
+    fn locates_invalid_transform_step() {
+        let (location, message) = build_error(&format!(r#"{{ {}, "objects": [
+            {{ "type": "transform", "object": {{ "type": "sphere", "center": [0.0, 0.0, 0.0], "radius": 0.5 }},
+              "steps": [{{ "rotate_y": 15.0 }}, {{ "translate": [1.0, 0.0, 0.0], "scale": [2.0, 2.0, 2.0] }}] }}] }}"#, CAMERA));
+        assert_eq!(location, "objects[0].steps[1]");
+        assert!(message.contains("expected exactly one of"), "{}", message);
+    }
+
+    #[test]
+    fn locates_singular_transform() {
+        let (location, message) = build_error(&format!(r#"{{ {}, "objects": [
+            {{ "type": "transform", "object": {{ "type": "sphere", "center": [0.0, 0.0, 0.0], "radius": 0.5 }},
+              "steps": [{{ "scale": [1.0, 0.0, 1.0] }}] }}] }}"#, CAMERA));
+        assert_eq!(location, "objects[0].steps");
+        assert!(message.contains("not invertible"), "{}", message);
+    }
+}
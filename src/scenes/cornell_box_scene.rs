@@ -0,0 +1,41 @@
+use std::sync::Arc;
+use crate::hittable::HittableList;
+use crate::quad::{make_box, Quad};
+use crate::transform::Transform;
+use crate::camera::Camera;
+use crate::integrator::MisIntegrator;
+use crate::vector3::Vector3;
+use crate::material::{DiffuseLight, Lambertian};
+use crate::background::SolidBackground;
+
+pub fn cornell_box_scene() -> (HittableList, Camera) {
+    let mut world = HittableList::new();
+
+    let red = Arc::new(Lambertian::new(Vector3::new(0.65, 0.05, 0.05)));
+    let white = Arc::new(Lambertian::new(Vector3::new(0.73, 0.73, 0.73)));
+    let green = Arc::new(Lambertian::new(Vector3::new(0.12, 0.45, 0.15)));
+    let light = Arc::new(DiffuseLight::new(Vector3::new(15.0, 15.0, 15.0)));
+
+    world.push(Quad::new(Vector3::new(555.0, 0.0, 0.0), Vector3::new(0.0, 555.0, 0.0), Vector3::new(0.0, 0.0, 555.0), Some(green)));
+    world.push(Quad::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 555.0, 0.0), Vector3::new(0.0, 0.0, 555.0), Some(red)));
+    world.push(Quad::new(Vector3::new(343.0, 554.0, 332.0), Vector3::new(-130.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -105.0), Some(light)));
+    world.push(Quad::new(Vector3::new(0.0, 0.0, 0.0), Vector3::new(555.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 555.0), Some(white.clone())));
+    world.push(Quad::new(Vector3::new(555.0, 555.0, 555.0), Vector3::new(-555.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -555.0), Some(white.clone())));
+    world.push(Quad::new(Vector3::new(0.0, 0.0, 555.0), Vector3::new(555.0, 0.0, 0.0), Vector3::new(0.0, 555.0, 0.0), Some(white.clone())));
+
+    let box1 = Arc::new(make_box(Vector3::new(0.0, 0.0, 0.0), Vector3::new(165.0, 330.0, 165.0), Some(white.clone())));
+    world.push(Transform::translate(Arc::new(Transform::rotate_y(box1, 15.0)), Vector3::new(265.0, 0.0, 295.0)));
+
+    let box2 = Arc::new(make_box(Vector3::new(0.0, 0.0, 0.0), Vector3::new(165.0, 165.0, 165.0), Some(white)));
+    world.push(Transform::translate(Arc::new(Transform::rotate_y(box2, -18.0)), Vector3::new(130.0, 0.0, 65.0)));
+
+    let mut camera = Camera::new(1.0, 600, 200, 50, 40.0, Vector3::new(278.0, 278.0, -800.0), Vector3::new(278.0, 278.0, 0.0), Vector3::new(0.0, 1.0, 0.0), 0.0, 10.0, 0.0, 0.0);
+    camera.background = Arc::new(SolidBackground::new(Vector3::new(0.0, 0.0, 0.0)));
+
+    let mut lights = HittableList::new();
+    lights.push(Quad::new(Vector3::new(343.0, 554.0, 332.0), Vector3::new(-130.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -105.0), None));
+    camera.lights = Some(Arc::new(lights));
+    camera.integrator = Arc::new(MisIntegrator::default());
+
+    (world, camera)
+}
@@ -4,26 +4,85 @@ pub mod bouncing_spheres_scene;
 pub mod checkered_spheres_scene;
 pub mod earth_scene;
 pub mod perlin_scene;
+pub mod cornell_box_scene;
+pub mod cornell_smoke_scene;
+pub mod random_spheres;
+
+use std::fmt;
+use std::str::FromStr;
 
 use crate::hittable::HittableList;
 use crate::camera::Camera;
+use crate::rng::Rng;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Scene {
     CoverScene,
     DepthOfFieldScene,
     BouncingSpheresScene,
     CheckeredSpheresScene,
     EarthScene,
-    PerlinScene
+    PerlinScene,
+    CornellBoxScene,
+    CornellSmokeScene
 }
 
-pub fn get_scene(scene: Scene) -> (HittableList, Camera) {
-    match scene {
-        Scene::CoverScene => cover_scene::cover_scene(),
+impl Scene {
+    pub const ALL: [Scene; 8] = [
+        Scene::CoverScene,
+        Scene::DepthOfFieldScene,
+        Scene::BouncingSpheresScene,
+        Scene::CheckeredSpheresScene,
+        Scene::EarthScene,
+        Scene::PerlinScene,
+        Scene::CornellBoxScene,
+        Scene::CornellSmokeScene,
+    ];
+
+    // Name used on the command line, which is also the default output file name.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scene::CoverScene => "cover",
+            Scene::DepthOfFieldScene => "depth-of-field",
+            Scene::BouncingSpheresScene => "bouncing-spheres",
+            Scene::CheckeredSpheresScene => "checkered-spheres",
+            Scene::EarthScene => "earth",
+            Scene::PerlinScene => "perlin",
+            Scene::CornellBoxScene => "cornell-box",
+            Scene::CornellSmokeScene => "cornell-smoke",
+        }
+    }
+}
+
+impl fmt::Display for Scene {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Scene {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Scene::ALL.into_iter().find(|scene| scene.name() == name).ok_or_else(|| {
+            let names: Vec<&str> = Scene::ALL.iter().map(|scene| scene.name()).collect();
+            format!("unknown scene \"{}\", expected one of: {}", name, names.join(", "))
+        })
+    }
+}
+
+pub fn get_scene(scene: Scene, seed: u64) -> (HittableList, Camera) {
+    let mut rng = Rng::new(seed);
+    let (world, mut camera) = match scene {
+        Scene::CoverScene => cover_scene::cover_scene(&mut rng),
         Scene::DepthOfFieldScene => depth_of_field_scene::depth_of_field_scene(),
-        Scene::BouncingSpheresScene => bouncing_spheres_scene::bouncing_spheres_scene(),
+        Scene::BouncingSpheresScene => bouncing_spheres_scene::bouncing_spheres_scene(&mut rng),
         Scene::CheckeredSpheresScene => checkered_spheres_scene::checkered_spheres_scene(),
         Scene::EarthScene => earth_scene::earth_scene(),
-        Scene::PerlinScene => perlin_scene::perlin_scene()
-    }
+        Scene::PerlinScene => perlin_scene::perlin_scene(&mut rng),
+        Scene::CornellBoxScene => cornell_box_scene::cornell_box_scene(),
+        Scene::CornellSmokeScene => cornell_smoke_scene::cornell_smoke_scene()
+    };
+    camera.seed = seed;
+    (world, camera)
 }
\ No newline at end of file
@@ -5,11 +5,12 @@ use crate::camera::Camera;
 use crate::vector3::Vector3;
 use crate::material::Lambertian;
 use crate::texture::NoiseTexture;
+use crate::rng::Rng;
 
-pub fn perlin_scene() -> (HittableList, Camera) {
+pub fn perlin_scene(rng: &mut Rng) -> (HittableList, Camera) {
     let mut world = HittableList::new();
 
-    let perlin_texture = Arc::new(NoiseTexture::new(4.0));
+    let perlin_texture = Arc::new(NoiseTexture::new(4.0, rng));
     let perlin_material = Arc::new(Lambertian::new_from_texture(perlin_texture));
     world.push(Sphere::new(Vector3::new(0.0, -1000.0, 0.0), 1000.0, Some(perlin_material.clone())));
     world.push(Sphere::new(Vector3::new(0.0, 2.0, 0.0), 2.0, Some(perlin_material.clone())));
@@ -0,0 +1,44 @@
+use std::sync::Arc;
+use crate::hittable::HittableList;
+use crate::rng::Rng;
+use crate::sphere::Sphere;
+use crate::moving_sphere::MovingSphere;
+use crate::vector3::Vector3;
+use crate::material::{Dielectric, Lambertian, Metal};
+
+// The 22x22 grid of small diffuse, metal and glass spheres from the cover of "Ray Tracing in One
+// Weekend", leaving room for a big sphere at (4, 1, 0). With a bounce_height above zero the
+// diffuse spheres move up by a random amount up to it between times 0 and 1.
+pub fn random_spheres(rng: &mut Rng, bounce_height: f64) -> HittableList {
+    let mut spheres = HittableList::new();
+
+    for a in -11..11 {
+        for b in -11..11 {
+            let choose_mat = rng.random_double(0.0, 1.0);
+            let center = Vector3::new((a as f64) + rng.random_double(0.0, 0.9), 0.2, (b as f64) + rng.random_double(0.0, 0.9));
+
+            if (center - Vector3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+                if choose_mat < 0.8 {
+                    let albedo = Vector3::random(rng, 0.0, 1.0) * Vector3::random(rng, 0.0, 1.0);
+                    let sphere_material = Arc::new(Lambertian::new(albedo));
+                    if bounce_height > 0.0 {
+                        let center2 = center + Vector3::new(0.0, rng.random_double(0.0, bounce_height), 0.0);
+                        spheres.push(MovingSphere::new(center, center2, 0.0, 1.0, 0.2, Some(sphere_material)));
+                    } else {
+                        spheres.push(Sphere::new(center, 0.2, Some(sphere_material)));
+                    }
+                } else if choose_mat < 0.95 {
+                    let albedo = Vector3::random(rng, 0.5, 1.0);
+                    let fuzz = rng.random_double(0.0, 0.5);
+                    let sphere_material = Arc::new(Metal::new(albedo, fuzz));
+                    spheres.push(Sphere::new(center, 0.2, Some(sphere_material)));
+                } else {
+                    let sphere_material = Arc::new(Dielectric::new(1.5));
+                    spheres.push(Sphere::new(center, 0.2, Some(sphere_material)));
+                }
+            }
+        }
+    }
+
+    spheres
+}
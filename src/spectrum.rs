@@ -0,0 +1,308 @@
+use std::ops::{Add, Div, Mul};
+use std::sync::OnceLock;
+
+use crate::vector3::Vector3;
+
+// Range of wavelengths rendered, in nanometers.
+pub const LAMBDA_MIN: f64 = 360.0;
+pub const LAMBDA_MAX: f64 = 830.0;
+
+// Wavelengths carried by each camera path.
+pub const SPECTRUM_SAMPLES: usize = 4;
+
+// Values of a spectral distribution at the wavelengths of a path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SampledSpectrum {
+    values: [f64; SPECTRUM_SAMPLES],
+}
+
+impl SampledSpectrum {
+    pub fn new(values: [f64; SPECTRUM_SAMPLES]) -> Self { SampledSpectrum { values } }
+    pub fn constant(value: f64) -> Self { SampledSpectrum { values: [value; SPECTRUM_SAMPLES] } }
+    pub fn values(&self) -> [f64; SPECTRUM_SAMPLES] { self.values }
+    pub fn max_value(&self) -> f64 { self.values.iter().copied().fold(f64::MIN, f64::max) }
+    pub fn is_zero(&self) -> bool { self.values.iter().all(|&v| v == 0.0) }
+
+    // Upsamples an RGB reflectance or other unitless color, such as an albedo or a BSDF value.
+    pub fn from_rgb(rgb: Vector3, wavelengths: &SampledWavelengths) -> Self {
+        SampledSpectrum { values: wavelengths.lambda.map(|lambda| rgb_to_spectrum(rgb, lambda)) }
+    }
+
+    // Upsamples an RGB emission, as a reflectance lit by the D65 white point so that white
+    // lights stay white.
+    pub fn from_rgb_illuminant(rgb: Vector3, wavelengths: &SampledWavelengths) -> Self {
+        let scale = calibration().d65_scale;
+        SampledSpectrum { values: wavelengths.lambda.map(|lambda| rgb_to_spectrum(rgb, lambda) * scale * d65(lambda)) }
+    }
+
+    // Monte Carlo estimate of the CIE XYZ color of the spectrum, given the wavelengths it was
+    // sampled at.
+    pub fn to_xyz(&self, wavelengths: &SampledWavelengths) -> Vector3 {
+        let mut xyz = Vector3::new(0.0, 0.0, 0.0);
+        for i in 0..SPECTRUM_SAMPLES {
+            if wavelengths.pdf[i] > 0.0 {
+                xyz = xyz + (self.values[i] / wavelengths.pdf[i]) * cie_xyz(wavelengths.lambda[i]);
+            }
+        }
+        xyz / (SPECTRUM_SAMPLES as f64 * calibration().y_integral)
+    }
+
+    // Linear sRGB, white balanced so that a white light on a white surface comes out white.
+    pub fn to_rgb(&self, wavelengths: &SampledWavelengths) -> Vector3 {
+        xyz_to_linear_srgb(self.to_xyz(wavelengths)) * calibration().white_balance
+    }
+}
+
+impl Add for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn add(self, other: SampledSpectrum) -> SampledSpectrum {
+        SampledSpectrum { values: std::array::from_fn(|i| self.values[i] + other.values[i]) }
+    }
+}
+
+impl Mul for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn mul(self, other: SampledSpectrum) -> SampledSpectrum {
+        SampledSpectrum { values: std::array::from_fn(|i| self.values[i] * other.values[i]) }
+    }
+}
+
+impl Mul<f64> for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn mul(self, scalar: f64) -> SampledSpectrum { SampledSpectrum { values: self.values.map(|v| v * scalar) } }
+}
+
+impl Mul<SampledSpectrum> for f64 {
+    type Output = SampledSpectrum;
+    fn mul(self, spectrum: SampledSpectrum) -> SampledSpectrum { spectrum * self }
+}
+
+impl Div<f64> for SampledSpectrum {
+    type Output = SampledSpectrum;
+    fn div(self, scalar: f64) -> SampledSpectrum { SampledSpectrum { values: self.values.map(|v| v / scalar) } }
+}
+
+// Hero wavelength sampling: the first wavelength is importance sampled over the visible
+// range, and the others are placed at even offsets from it in sample space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SampledWavelengths {
+    lambda: [f64; SPECTRUM_SAMPLES],
+    pdf: [f64; SPECTRUM_SAMPLES],
+}
+
+impl SampledWavelengths {
+    pub fn sample_visible(u: f64) -> Self {
+        let mut lambda = [0.0; SPECTRUM_SAMPLES];
+        let mut pdf = [0.0; SPECTRUM_SAMPLES];
+        for i in 0..SPECTRUM_SAMPLES {
+            let up = (u + i as f64 / SPECTRUM_SAMPLES as f64).fract();
+            lambda[i] = sample_visible_wavelength(up);
+            pdf[i] = visible_wavelength_pdf(lambda[i]);
+        }
+        SampledWavelengths { lambda, pdf }
+    }
+
+    pub fn hero(&self) -> f64 { self.lambda[0] }
+    pub fn lambda(&self) -> [f64; SPECTRUM_SAMPLES] { self.lambda }
+    pub fn pdf(&self) -> [f64; SPECTRUM_SAMPLES] { self.pdf }
+
+    pub fn secondary_terminated(&self) -> bool { self.pdf[1..].iter().all(|&pdf| pdf == 0.0) }
+
+    // Keeps only the hero wavelength, for paths through wavelength dependent scattering such
+    // as dispersion where the other wavelengths would have gone elsewhere.
+    pub fn terminate_secondary(&mut self) {
+        if self.secondary_terminated() {
+            return;
+        }
+        for pdf in &mut self.pdf[1..] {
+            *pdf = 0.0;
+        }
+        self.pdf[0] /= SPECTRUM_SAMPLES as f64;
+    }
+}
+
+// Density roughly following the eye's sensitivity, from Radziszewski et al. 2009.
+fn sample_visible_wavelength(u: f64) -> f64 {
+    (538.0 - 138.888889 * (0.85691062 - 1.82750197 * u).atanh()).clamp(LAMBDA_MIN, LAMBDA_MAX)
+}
+
+fn visible_wavelength_pdf(lambda: f64) -> f64 {
+    if !(LAMBDA_MIN..=LAMBDA_MAX).contains(&lambda) {
+        return 0.0;
+    }
+    0.0039398042 / (0.0072 * (lambda - 538.0)).cosh().powi(2)
+}
+
+// CIE 1931 color matching functions, using the multi-lobe Gaussian fit of Wyman et al. 2013.
+pub fn cie_xyz(lambda: f64) -> Vector3 {
+    let g = |mu: f64, sigma_below: f64, sigma_above: f64| {
+        let t = (lambda - mu) / if lambda < mu { sigma_below } else { sigma_above };
+        (-0.5 * t * t).exp()
+    };
+    Vector3::new(
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+pub fn xyz_to_linear_srgb(xyz: Vector3) -> Vector3 {
+    Vector3::new(
+        3.2404542 * xyz.x() - 1.5371385 * xyz.y() - 0.4985314 * xyz.z(),
+        -0.9692660 * xyz.x() + 1.8760108 * xyz.y() + 0.0415560 * xyz.z(),
+        0.0556434 * xyz.x() - 0.2040259 * xyz.y() + 1.0572252 * xyz.z(),
+    )
+}
+
+// Smits 1999: reflectance spectra for white and the primary and secondary colors, in ten
+// bins from 380 to 720 nm, combined so that the result is as smooth as possible.
+const SMITS_WHITE: [f64; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const SMITS_CYAN: [f64; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const SMITS_MAGENTA: [f64; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const SMITS_YELLOW: [f64; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED: [f64; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN: [f64; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const SMITS_BLUE: [f64; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+fn rgb_to_spectrum(rgb: Vector3, lambda: f64) -> f64 {
+    let (r, g, b) = (rgb.x().max(0.0), rgb.y().max(0.0), rgb.z().max(0.0));
+    let smits = |table: &[f64; 10]| smits_value(table, lambda);
+    if r <= g && r <= b {
+        r * smits(&SMITS_WHITE)
+            + if g <= b {
+                (g - r) * smits(&SMITS_CYAN) + (b - g) * smits(&SMITS_BLUE)
+            } else {
+                (b - r) * smits(&SMITS_CYAN) + (g - b) * smits(&SMITS_GREEN)
+            }
+    } else if g <= r && g <= b {
+        g * smits(&SMITS_WHITE)
+            + if r <= b {
+                (r - g) * smits(&SMITS_MAGENTA) + (b - r) * smits(&SMITS_BLUE)
+            } else {
+                (b - g) * smits(&SMITS_MAGENTA) + (r - b) * smits(&SMITS_RED)
+            }
+    } else {
+        b * smits(&SMITS_WHITE)
+            + if r <= g {
+                (r - b) * smits(&SMITS_YELLOW) + (g - r) * smits(&SMITS_GREEN)
+            } else {
+                (g - b) * smits(&SMITS_YELLOW) + (r - g) * smits(&SMITS_RED)
+            }
+    }
+}
+
+// Linear interpolation between the bin centers, constant past the first and last.
+fn smits_value(table: &[f64; 10], lambda: f64) -> f64 {
+    let bin_width = (720.0 - 380.0) / 10.0;
+    let x = ((lambda - 380.0) / bin_width - 0.5).clamp(0.0, 9.0);
+    let i = (x as usize).min(8);
+    let t = x - i as f64;
+    (1.0 - t) * table[i] + t * table[i + 1]
+}
+
+// CIE standard illuminant D65 in 10 nm steps from 360 to 830 nm.
+const D65: [f64; 48] = [
+    46.6383, 52.0891, 49.9755, 54.6482, 82.7549, 91.486, 93.4318, 86.6823, 104.865, 117.008, 117.812, 114.861,
+    115.923, 108.811, 109.354, 107.802, 104.79, 107.689, 104.405, 104.046, 100.0, 96.3342, 95.788, 88.6856,
+    90.0062, 89.5991, 87.6987, 83.2886, 83.6992, 80.0268, 80.2146, 82.2778, 78.2842, 69.7213, 71.6091, 74.349,
+    61.604, 69.8856, 75.087, 63.5927, 46.4182, 66.8054, 63.3828, 64.304, 59.4519, 51.959, 57.4406, 60.3125,
+];
+
+fn d65(lambda: f64) -> f64 {
+    let x = ((lambda - LAMBDA_MIN) / 10.0).clamp(0.0, (D65.len() - 1) as f64);
+    let i = (x as usize).min(D65.len() - 2);
+    let t = x - i as f64;
+    (1.0 - t) * D65[i] + t * D65[i + 1]
+}
+
+// Normalization constants, integrated once from the functions above so that they agree with
+// each other exactly.
+struct Calibration {
+    // Integral of the y matching function, so that Y is 1 for a constant spectrum of 1.
+    y_integral: f64,
+    // Scales D65 to a luminance of 1.
+    d65_scale: f64,
+    // Inverse of the linear sRGB color of the scaled D65.
+    white_balance: Vector3,
+}
+
+fn calibration() -> &'static Calibration {
+    static CALIBRATION: OnceLock<Calibration> = OnceLock::new();
+    CALIBRATION.get_or_init(|| {
+        let steps = (LAMBDA_MAX - LAMBDA_MIN) as usize;
+        let mut y_integral = 0.0;
+        let mut d65_xyz = Vector3::new(0.0, 0.0, 0.0);
+        for step in 0..steps {
+            let lambda = LAMBDA_MIN + step as f64 + 0.5;
+            y_integral += cie_xyz(lambda).y();
+            d65_xyz = d65_xyz + d65(lambda) * cie_xyz(lambda);
+        }
+        let d65_scale = y_integral / d65_xyz.y();
+        let white = xyz_to_linear_srgb(d65_xyz * (d65_scale / y_integral));
+        Calibration {
+            y_integral,
+            d65_scale,
+            white_balance: Vector3::new(1.0 / white.x(), 1.0 / white.y(), 1.0 / white.z()),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Averages the color of a spectrum over evenly spaced hero wavelengths, which converges much
+    // faster than random ones.
+    fn average_rgb(spectrum: impl Fn(&SampledWavelengths) -> SampledSpectrum) -> Vector3 {
+        let n = 4000;
+        let mut sum = Vector3::new(0.0, 0.0, 0.0);
+        for i in 0..n {
+            let wavelengths = SampledWavelengths::sample_visible((i as f64 + 0.5) / n as f64);
+            sum = sum + spectrum(&wavelengths).to_rgb(&wavelengths);
+        }
+        sum / n as f64
+    }
+
+    fn assert_rgb_close(actual: Vector3, expected: Vector3, tolerance: f64) {
+        for axis in 0..3 {
+            assert!((actual[axis] - expected[axis]).abs() <= tolerance, "{:?} is not within {} of {:?}", actual, tolerance, expected);
+        }
+    }
+
+    #[test]
+    fn rgb_round_trips_through_spectra() {
+        // White and grey come back almost exactly; Smits' smooth primaries only approximately.
+        for (rgb, tolerance) in [
+            (Vector3::new(1.0, 1.0, 1.0), 0.002),
+            (Vector3::new(0.5, 0.5, 0.5), 0.001),
+            (Vector3::new(1.0, 0.0, 0.0), 0.04),
+            (Vector3::new(0.0, 1.0, 0.0), 0.04),
+            (Vector3::new(0.0, 0.0, 1.0), 0.04),
+        ] {
+            assert_rgb_close(average_rgb(|w| SampledSpectrum::from_rgb_illuminant(rgb, w)), rgb, tolerance);
+        }
+    }
+
+    #[test]
+    fn calibrated_d65_is_white() {
+        let scale = calibration().d65_scale;
+        let white = average_rgb(|w| SampledSpectrum::new(w.lambda().map(|lambda| scale * d65(lambda))));
+        assert_rgb_close(white, Vector3::new(1.0, 1.0, 1.0), 1e-4);
+    }
+
+    #[test]
+    fn cie_fit_integrates_to_reference() {
+        // Integrals of the tabulated CIE 1931 matching functions, which are all close to Y.
+        const CIE_Y_INTEGRAL: f64 = 106.856895;
+        let steps = 47_000;
+        let step = (LAMBDA_MAX - LAMBDA_MIN) / steps as f64;
+        let mut xyz = Vector3::new(0.0, 0.0, 0.0);
+        for i in 0..steps {
+            xyz = xyz + step * cie_xyz(LAMBDA_MIN + (i as f64 + 0.5) * step);
+        }
+        for axis in 0..3 {
+            assert!((xyz[axis] / CIE_Y_INTEGRAL - 1.0).abs() < 0.005, "{:?}", xyz);
+        }
+        assert!((calibration().y_integral / xyz.y() - 1.0).abs() < 1e-6);
+    }
+}
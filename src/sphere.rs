@@ -6,6 +6,9 @@ use crate::vector3::Vector3;
 use crate::ray::Ray;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::onb::Onb;
+use crate::sampler::{sample_uniform_sphere, Sampler};
 
 pub struct Sphere {
     center: Vector3,
@@ -53,9 +56,61 @@ impl Hittable for Sphere {
         rec.p = r.at(rec.t);
         let outward_normal = (rec.p - self.center()) / self.radius();
         rec.set_face_normal(r, &outward_normal);
+        rec.tangent = sphere_tangent(outward_normal);
         (rec.u, rec.v) = self.get_sphere_uv(outward_normal);
         rec.mat = self.mat.clone();
 
         true
     }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        let rvec = Vector3::new(self.radius(), self.radius(), self.radius());
+        *output_box = Aabb::new(self.center() - rvec, self.center() + rvec);
+        true
+    }
+
+    fn pdf_value(&self, origin: Vector3, direction: Vector3) -> f64 {
+        // This method only works for stationary spheres, sampled uniformly over the cone
+        // of directions they subtend from origin.
+        let mut rec = HitRecord::default();
+        if !self.hit(&Ray::new(origin, direction, 0.0), 0.001, f64::INFINITY, &mut rec) {
+            return 0.0;
+        }
+
+        // From inside, the sphere covers every direction, and random samples them all uniformly.
+        let dist_squared = (self.center() - origin).length_squared();
+        if dist_squared <= self.radius() * self.radius() {
+            return 1.0 / (4.0 * PI);
+        }
+        let cos_theta_max = (1.0 - self.radius() * self.radius() / dist_squared).max(0.0).sqrt();
+        let solid_angle = 2.0 * PI * (1.0 - cos_theta_max);
+
+        1.0 / solid_angle
+    }
+
+    fn random(&self, origin: Vector3, sampler: &mut dyn Sampler) -> Vector3 {
+        let direction = self.center() - origin;
+        let distance_squared = direction.length_squared();
+        if distance_squared <= self.radius() * self.radius() {
+            return sample_uniform_sphere(sampler.get_2d());
+        }
+        let uvw = Onb::new(direction);
+        uvw.transform(random_to_sphere(self.radius(), distance_squared, sampler.get_2d()))
+    }
+}
+
+// Direction of increasing u on the unit sphere at p, along its circle of latitude. Zero at the poles.
+pub fn sphere_tangent(p: Vector3) -> Vector3 {
+    let tangent = Vector3::new(p.z(), 0.0, -p.x());
+    if tangent.near_zero() { tangent } else { tangent.unit_vector() }
+}
+
+fn random_to_sphere(radius: f64, distance_squared: f64, (r1, r2): (f64, f64)) -> Vector3 {
+    let z = 1.0 + r2 * ((1.0 - radius * radius / distance_squared).max(0.0).sqrt() - 1.0);
+
+    let phi = 2.0 * PI * r1;
+    let x = phi.cos() * (1.0 - z * z).sqrt();
+    let y = phi.sin() * (1.0 - z * z).sqrt();
+
+    Vector3::new(x, y, z)
 }
\ No newline at end of file
@@ -0,0 +1,100 @@
+// Helpers shared by the unit tests of several modules.
+
+use std::f64::consts::PI;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::hittable::HitRecord;
+use crate::material::{Material, ScatterRecord};
+use crate::pdf::Pdf;
+use crate::ray::Ray;
+use crate::sampler::{sample_uniform_sphere, IndependentSampler};
+use crate::vector3::Vector3;
+
+const SAMPLES: usize = 200_000;
+
+pub fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+    assert!((actual - expected).abs() <= tolerance, "{} is not within {} of {}", actual, tolerance, expected);
+}
+
+// Integrates f over the unit sphere with the midpoints of a grid of equal-area cells.
+pub fn integrate_sphere(f: impl Fn(Vector3) -> f64) -> f64 {
+    let (rows, columns) = (400, 800);
+    let mut sum = 0.0;
+    for i in 0..rows {
+        for j in 0..columns {
+            sum += f(sample_uniform_sphere(((i as f64 + 0.5) / rows as f64, (j as f64 + 0.5) / columns as f64)));
+        }
+    }
+    sum * 4.0 * PI / (rows * columns) as f64
+}
+
+// Averages f over directions drawn from pdf.
+pub fn average_over_samples(pdf: &dyn Pdf, f: impl Fn(Vector3) -> f64) -> f64 {
+    let mut sampler = IndependentSampler::default();
+    (0..SAMPLES).map(|_| f(pdf.generate(&mut sampler))).sum::<f64>() / SAMPLES as f64
+}
+
+// A pdf must integrate to one, and generate must draw directions with the density that
+// value reports: the mean of value over samples estimates the integral of value squared.
+pub fn assert_consistent_pdf(pdf: &dyn Pdf) {
+    assert_close(integrate_sphere(|w| pdf.value(w)), 1.0, 0.01);
+    let expected = integrate_sphere(|w| pdf.value(w) * pdf.value(w));
+    assert_close(average_over_samples(pdf, |w| pdf.value(w)), expected, 0.02 * expected);
+}
+
+// Averaging eval / pdf over the material's own samples must give the integral of eval. The
+// surface lies in the xy plane facing +z, with its tangent along x.
+pub fn assert_eval_matches_sampling(material: &dyn Material, wo: Vector3) {
+    let rec = HitRecord {
+        normal: Vector3::new(0.0, 0.0, 1.0),
+        tangent: Vector3::new(1.0, 0.0, 0.0),
+        front_face: true,
+        ..HitRecord::default()
+    };
+    let r_in = Ray::new(wo, -wo, 0.0);
+    let mut srec = ScatterRecord::default();
+    assert!(material.scatter(&r_in, &rec, &mut srec, &mut IndependentSampler::default()));
+    let pdf = srec.pdf.expect("material should be sampled through a pdf");
+
+    let eval = |w: Vector3| material.eval(&r_in, &rec, &Ray::new(rec.p, w, 0.0)).x();
+    let expected = integrate_sphere(eval);
+    let estimate = average_over_samples(pdf.as_ref(), |w| {
+        let density = pdf.value(w);
+        if density > 0.0 { eval(w) / density } else { 0.0 }
+    });
+    assert_close(estimate, expected, 0.02 * expected);
+}
+
+// Fresh directory under the system temp directory for files a test reads or writes, removed
+// with everything in it when dropped.
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("ray-tracer-test-{}-{}", process::id(), NEXT.fetch_add(1, Ordering::Relaxed));
+        let path = std::env::temp_dir().join(name);
+        fs::create_dir_all(&path).unwrap();
+        TempDir { path }
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    // Writes a file into the directory and returns its path.
+    pub fn write(&self, name: &str, contents: &str) -> PathBuf {
+        let path = self.path.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
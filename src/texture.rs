@@ -1,12 +1,19 @@
+use std::path::Path;
 use std::sync::Arc;
-use image::RgbImage;
+use image::{ImageError, RgbImage};
 
 
 use crate::perlin::Perlin;
+use crate::rng::Rng;
 use crate::vector3::Vector3;
 
-pub trait Texture {
+pub trait Texture: Send + Sync {
     fn value(&self, u: f64, v: f64, p: &Vector3) -> Vector3;
+    // Single channel value for textures driving a scalar parameter, averaging the channels.
+    fn scalar_value(&self, u: f64, v: f64, p: &Vector3) -> f64 {
+        let c = self.value(u, v, p);
+        (c.x() + c.y() + c.z()) / 3.0
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -61,8 +68,11 @@ pub struct ImageTexture {
 impl ImageTexture {
     pub fn new(image_name: &str) -> Self {
         let image_path = format!("images/{}", image_name);
-        let image = image::open(image_path).expect("Failed to open image").to_rgb8();
-        Self { image }
+        Self::from_path(image_path).expect("Failed to open image")
+    }
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, ImageError> {
+        let image = image::open(path)?.to_rgb8();
+        Ok(Self { image })
     }
 }
 
@@ -89,7 +99,7 @@ pub struct NoiseTexture {
 }
 
 impl NoiseTexture {
-    pub fn new(scale: f64) -> Self { NoiseTexture { noise: Perlin::new(), scale } }
+    pub fn new(scale: f64, rng: &mut Rng) -> Self { NoiseTexture { noise: Perlin::new(rng), scale } }
 }
 
 impl Texture for NoiseTexture {
@@ -0,0 +1,247 @@
+use std::str::FromStr;
+
+use image::Rgb;
+
+use crate::rng::Rng;
+use crate::vector3::Vector3;
+
+// Curve that compresses scene radiance into the displayable [0, 1] range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ToneMap {
+    // Clip each channel at 1.0.
+    Clamp,
+    // L / (1 + L) on luminance, keeping the hue.
+    Reinhard,
+    // Reinhard with luminance white_point mapped to 1.0 instead of only approaching it.
+    ExtendedReinhard { white_point: f64 },
+    // Narkowicz's curve fit of the ACES filmic reference transform.
+    Aces,
+    // John Hable's filmic curve from Uncharted 2, with luminance white_point mapped to 1.0.
+    Uncharted2 { white_point: f64 },
+}
+
+// Hable's white point, and the one used for extended Reinhard when none is given.
+pub const DEFAULT_WHITE_POINT: f64 = 11.2;
+
+impl ToneMap {
+    pub fn apply(&self, color: Vector3) -> Vector3 {
+        match *self {
+            ToneMap::Clamp => color,
+            ToneMap::Reinhard => scale_luminance(color, |l| l / (1.0 + l)),
+            ToneMap::ExtendedReinhard { white_point } => {
+                let white_squared = white_point * white_point;
+                scale_luminance(color, |l| l * (1.0 + l / white_squared) / (1.0 + l))
+            }
+            ToneMap::Aces => map_channels(color, |c| {
+                let c = 0.6 * c;
+                (c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)
+            }),
+            ToneMap::Uncharted2 { white_point } => {
+                // The 2.0 is the exposure bias from Hable's reference implementation.
+                let white_scale = 1.0 / hable_partial(white_point);
+                map_channels(color, |c| hable_partial(2.0 * c) * white_scale)
+            }
+        }
+    }
+}
+
+// Parses the names used on the command line and in scene files: clamp, reinhard,
+// extended-reinhard, aces or uncharted2, the ones with a white point optionally followed by
+// it, e.g. "extended-reinhard:4".
+impl FromStr for ToneMap {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (name, white_point) = match text.split_once(':') {
+            Some((name, white_point)) => {
+                let white_point = white_point.parse::<f64>().ok().filter(|w| *w > 0.0)
+                    .ok_or_else(|| format!("invalid white point \"{}\", expected a positive number", white_point))?;
+                (name, Some(white_point))
+            }
+            None => (text, None),
+        };
+        let white_point_or_default = white_point.unwrap_or(DEFAULT_WHITE_POINT);
+        let tone_map = match name {
+            "clamp" => ToneMap::Clamp,
+            "reinhard" => ToneMap::Reinhard,
+            "extended-reinhard" => ToneMap::ExtendedReinhard { white_point: white_point_or_default },
+            "aces" => ToneMap::Aces,
+            "uncharted2" => ToneMap::Uncharted2 { white_point: white_point_or_default },
+            _ => return Err(format!(
+                "unknown tone map \"{}\", expected one of: clamp, reinhard, extended-reinhard, aces, uncharted2", name,
+            )),
+        };
+        if white_point.is_some() && !matches!(tone_map, ToneMap::ExtendedReinhard { .. } | ToneMap::Uncharted2 { .. }) {
+            return Err(format!("tone map \"{}\" takes no white point", name));
+        }
+        Ok(tone_map)
+    }
+}
+
+// Encoding from linear light to the values stored in an 8-bit image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransferFunction {
+    // The piecewise sRGB curve, with its linear segment near black.
+    Srgb,
+    // A pure power curve, c^(1 / gamma).
+    Gamma(f64),
+}
+
+impl TransferFunction {
+    pub fn encode(&self, c: f64) -> f64 {
+        match *self {
+            TransferFunction::Srgb => {
+                if c <= 0.0031308 { 12.92 * c } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+            }
+            TransferFunction::Gamma(gamma) => c.powf(1.0 / gamma),
+        }
+    }
+
+    // The inverse of encode, from stored values back to linear light.
+    pub fn decode(&self, v: f64) -> f64 {
+        match *self {
+            TransferFunction::Srgb => {
+                if v <= 12.92 * 0.0031308 { v / 12.92 } else { ((v + 0.055) / 1.055).powf(2.4) }
+            }
+            TransferFunction::Gamma(gamma) => v.powf(gamma),
+        }
+    }
+}
+
+// Steps turning a linear radiance pixel into an 8-bit display pixel: exposure, tone mapping,
+// clipping to [0, 1], the transfer function, then quantization with optional dithering.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PostProcess {
+    // Exposure in stops; each step doubles the brightness.
+    pub exposure: f64,
+    pub tone_map: ToneMap,
+    pub transfer: TransferFunction,
+    // Adds up to one step of triangular noise before quantizing, hiding banding in gradients.
+    pub dither: bool,
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        PostProcess { exposure: 0.0, tone_map: ToneMap::Clamp, transfer: TransferFunction::Srgb, dither: false }
+    }
+}
+
+impl PostProcess {
+    // Display color in [0, 1] before quantization.
+    pub fn apply(&self, color: Vector3) -> Vector3 {
+        let exposed = 2.0_f64.powf(self.exposure) * color;
+        let mapped = self.tone_map.apply(exposed);
+        map_channels(mapped, |c| self.transfer.encode(c.clamp(0.0, 1.0)))
+    }
+
+    // The dither noise is keyed on the pixel coordinates so that output stays deterministic.
+    pub fn to_rgb8(&self, color: Vector3, x: u32, y: u32) -> Rgb<u8> {
+        let display = self.apply(color);
+        let mut rng = Rng::from_keys(&[x as u64, y as u64]);
+        let mut quantize = |c: f64| {
+            let noise = if self.dither { rng.random_double(0.0, 1.0) - rng.random_double(0.0, 1.0) } else { 0.0 };
+            (255.0 * c + 0.5 + noise).clamp(0.0, 255.0) as u8
+        };
+        Rgb([quantize(display.x()), quantize(display.y()), quantize(display.z())])
+    }
+}
+
+fn luminance(color: Vector3) -> f64 {
+    0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+}
+
+fn scale_luminance(color: Vector3, curve: impl Fn(f64) -> f64) -> Vector3 {
+    let l = luminance(color);
+    if l <= 0.0 {
+        return Vector3::new(0.0, 0.0, 0.0);
+    }
+    (curve(l) / l) * color
+}
+
+fn map_channels(color: Vector3, f: impl Fn(f64) -> f64) -> Vector3 {
+    Vector3::new(f(color.x()), f(color.y()), f(color.z()))
+}
+
+fn hable_partial(x: f64) -> f64 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grey(c: f64) -> Vector3 { Vector3::new(c, c, c) }
+
+    // Checks a curve on grey, whose luminance equals its channels: black stays black, brighter
+    // input never gets darker, and white_point, if any, maps to 1.
+    fn assert_curve(tone_map: ToneMap, white_point: Option<f64>) {
+        assert_eq!(tone_map.apply(grey(0.0)).x(), 0.0);
+        let mut previous = 0.0;
+        for i in 1..=2000 {
+            let mapped = tone_map.apply(grey(i as f64 * 0.01)).x();
+            assert!(mapped >= previous, "{:?} decreases at {}", tone_map, i as f64 * 0.01);
+            previous = mapped;
+        }
+        if let Some(white_point) = white_point {
+            assert!((tone_map.apply(grey(white_point)).x() - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn reinhard_halves_one() {
+        assert_curve(ToneMap::Reinhard, None);
+        assert!((ToneMap::Reinhard.apply(grey(1.0)).x() - 0.5).abs() < 1e-12);
+        assert!(ToneMap::Reinhard.apply(grey(1e6)).x() < 1.0);
+    }
+
+    #[test]
+    fn extended_reinhard_maps_white_point_to_one() {
+        assert_curve(ToneMap::ExtendedReinhard { white_point: 4.0 }, Some(4.0));
+    }
+
+    #[test]
+    fn aces_matches_reference_values() {
+        assert_curve(ToneMap::Aces, None);
+        // Narkowicz's fit gives about 0.8 for an input of 1 after the 0.6 exposure scale.
+        assert!((ToneMap::Aces.apply(grey(1.0 / 0.6)).x() - 2.54 / 3.16).abs() < 1e-12);
+    }
+
+    #[test]
+    fn uncharted2_maps_white_point_to_one() {
+        // The 2.0 exposure bias is applied before the curve, so the white point is reached at half.
+        let white_point = DEFAULT_WHITE_POINT;
+        assert_curve(ToneMap::Uncharted2 { white_point }, Some(white_point / 2.0));
+    }
+
+    #[test]
+    fn srgb_is_continuous_at_breakpoint() {
+        let srgb = TransferFunction::Srgb;
+        let breakpoint = 0.0031308;
+        assert!((srgb.encode(breakpoint) - 12.92 * breakpoint).abs() < 1e-15);
+        let power = 1.055 * breakpoint.powf(1.0 / 2.4) - 0.055;
+        assert!((srgb.encode(breakpoint) - power).abs() < 1e-6);
+        assert!((srgb.encode(breakpoint + 1e-9) - srgb.encode(breakpoint)).abs() < 1e-6);
+        assert!((srgb.encode(1.0) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn transfer_functions_round_trip() {
+        for transfer in [TransferFunction::Srgb, TransferFunction::Gamma(2.2)] {
+            for i in 0..=1000 {
+                let c = i as f64 / 1000.0;
+                assert!((transfer.decode(transfer.encode(c)) - c).abs() < 1e-12, "{:?} at {}", transfer, c);
+            }
+        }
+    }
+
+    #[test]
+    fn parses_tone_map_names() {
+        assert_eq!("aces".parse::<ToneMap>(), Ok(ToneMap::Aces));
+        assert_eq!("extended-reinhard:4".parse::<ToneMap>(), Ok(ToneMap::ExtendedReinhard { white_point: 4.0 }));
+        assert_eq!("uncharted2".parse::<ToneMap>(), Ok(ToneMap::Uncharted2 { white_point: DEFAULT_WHITE_POINT }));
+        assert!("reinhard:4".parse::<ToneMap>().is_err());
+        assert!("extended-reinhard:-1".parse::<ToneMap>().is_err());
+        assert!("filmic".parse::<ToneMap>().is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use std::ops::Mul;
+use std::sync::Arc;
+
+use crate::vector3::Vector3;
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable};
+use crate::aabb::Aabb;
+use crate::sampler::Sampler;
+
+// Affine transform stored as a row-major 4x4 matrix whose last row is (0, 0, 0, 1).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix4 {
+    m: [[f64; 4]; 4]
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Self { Matrix4 { m } }
+    pub fn identity() -> Self {
+        Matrix4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    pub fn translation(offset: Vector3) -> Self {
+        Matrix4::new([
+            [1.0, 0.0, 0.0, offset.x()],
+            [0.0, 1.0, 0.0, offset.y()],
+            [0.0, 0.0, 1.0, offset.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    pub fn scaling(factors: Vector3) -> Self {
+        Matrix4::new([
+            [factors.x(), 0.0, 0.0, 0.0],
+            [0.0, factors.y(), 0.0, 0.0],
+            [0.0, 0.0, factors.z(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    pub fn rotation_x(degrees: f64) -> Self {
+        let (sin_theta, cos_theta) = degrees.to_radians().sin_cos();
+        Matrix4::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, cos_theta, -sin_theta, 0.0],
+            [0.0, sin_theta, cos_theta, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    pub fn rotation_y(degrees: f64) -> Self {
+        let (sin_theta, cos_theta) = degrees.to_radians().sin_cos();
+        Matrix4::new([
+            [cos_theta, 0.0, sin_theta, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [-sin_theta, 0.0, cos_theta, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+    pub fn rotation_z(degrees: f64) -> Self {
+        let (sin_theta, cos_theta) = degrees.to_radians().sin_cos();
+        Matrix4::new([
+            [cos_theta, -sin_theta, 0.0, 0.0],
+            [sin_theta, cos_theta, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transform_point(&self, p: Vector3) -> Vector3 {
+        self.transform_vector(p) + Vector3::new(self.m[0][3], self.m[1][3], self.m[2][3])
+    }
+    pub fn transform_vector(&self, v: Vector3) -> Vector3 {
+        let m = &self.m;
+        Vector3::new(
+            m[0][0] * v.x() + m[0][1] * v.y() + m[0][2] * v.z(),
+            m[1][0] * v.x() + m[1][1] * v.y() + m[1][2] * v.z(),
+            m[2][0] * v.x() + m[2][1] * v.y() + m[2][2] * v.z(),
+        )
+    }
+
+    #[allow(clippy::needless_range_loop)]
+    pub fn transpose(&self) -> Matrix4 {
+        let mut t = [[0.0; 4]; 4];
+        for i in 0..4 {
+            for j in 0..4 {
+                t[i][j] = self.m[j][i];
+            }
+        }
+        Matrix4::new(t)
+    }
+
+    // Determinant of the linear 3x3 part, the factor by which the transform scales volumes.
+    pub fn determinant(&self) -> f64 {
+        let a = &self.m;
+        a[0][0] * (a[1][1] * a[2][2] - a[1][2] * a[2][1])
+            - a[0][1] * (a[1][0] * a[2][2] - a[1][2] * a[2][0])
+            + a[0][2] * (a[1][0] * a[2][1] - a[1][1] * a[2][0])
+    }
+
+    pub fn inverse(&self) -> Option<Matrix4> {
+        // Invert the linear 3x3 part with cofactors, then undo the translation: if M = [A | t]
+        // then M^-1 = [A^-1 | -A^-1 t].
+        let a = &self.m;
+        let cofactor = |r0: usize, r1: usize, c0: usize, c1: usize| a[r0][c0] * a[r1][c1] - a[r0][c1] * a[r1][c0];
+        let c00 = cofactor(1, 2, 1, 2);
+        let c01 = -cofactor(1, 2, 0, 2);
+        let c02 = cofactor(1, 2, 0, 1);
+        let det = self.determinant();
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let mut inv = [[0.0; 4]; 4];
+        inv[0][0] = c00 * inv_det;
+        inv[1][0] = c01 * inv_det;
+        inv[2][0] = c02 * inv_det;
+        inv[0][1] = -cofactor(0, 2, 1, 2) * inv_det;
+        inv[1][1] = cofactor(0, 2, 0, 2) * inv_det;
+        inv[2][1] = -cofactor(0, 2, 0, 1) * inv_det;
+        inv[0][2] = cofactor(0, 1, 1, 2) * inv_det;
+        inv[1][2] = -cofactor(0, 1, 0, 2) * inv_det;
+        inv[2][2] = cofactor(0, 1, 0, 1) * inv_det;
+        inv[3][3] = 1.0;
+
+        let linear = Matrix4::new(inv);
+        let t = linear.transform_vector(Vector3::new(a[0][3], a[1][3], a[2][3]));
+        inv[0][3] = -t.x();
+        inv[1][3] = -t.y();
+        inv[2][3] = -t.z();
+        Some(Matrix4::new(inv))
+    }
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self { Matrix4::identity() }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+    #[allow(clippy::needless_range_loop)]
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        let mut product = [[0.0; 4]; 4];
+        for i in 0..4 {
+            for j in 0..4 {
+                product[i][j] = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Matrix4::new(product)
+    }
+}
+
+// Instance of a shared object placed in the world by an affine transform. Rays are moved into
+// the object's space for intersection, and the hit point and normal are moved back out.
+pub struct Transform {
+    object: Arc<dyn Hittable>,
+    object_to_world: Matrix4,
+    world_to_object: Matrix4,
+}
+
+impl Transform {
+    // None if the matrix isn't invertible, e.g. because it scales an axis by zero.
+    pub fn new(object: Arc<dyn Hittable>, object_to_world: Matrix4) -> Option<Transform> {
+        let world_to_object = object_to_world.inverse()?;
+        Some(Transform { object, object_to_world, world_to_object })
+    }
+    pub fn translate(object: Arc<dyn Hittable>, offset: Vector3) -> Transform {
+        Transform { object, object_to_world: Matrix4::translation(offset), world_to_object: Matrix4::translation(-offset) }
+    }
+    pub fn rotate_y(object: Arc<dyn Hittable>, degrees: f64) -> Transform {
+        Transform { object, object_to_world: Matrix4::rotation_y(degrees), world_to_object: Matrix4::rotation_y(-degrees) }
+    }
+    pub fn matrix(&self) -> Matrix4 { self.object_to_world }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        // The direction is left unnormalized so that t means the same thing in both spaces.
+        let object_ray = Ray::new(
+            self.world_to_object.transform_point(r.orig()),
+            self.world_to_object.transform_vector(r.dir()),
+            r.time(),
+        ).with_key(r.key());
+
+        if !self.object.hit(&object_ray, t_min, t_max, rec) {
+            return false;
+        }
+
+        // Normals transform by the inverse transpose, which keeps them perpendicular to the
+        // surface under non-uniform scaling and preserves which side of it the ray is on.
+        rec.p = self.object_to_world.transform_point(rec.p);
+        rec.normal = self.world_to_object.transpose().transform_vector(rec.normal).unit_vector();
+        let tangent = self.object_to_world.transform_vector(rec.tangent);
+        rec.tangent = if tangent.near_zero() { tangent } else { tangent.unit_vector() };
+
+        true
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+        let mut object_box = Aabb::default();
+        if !self.object.bounding_box(time0, time1, &mut object_box) {
+            return false;
+        }
+
+        // Transform all eight corners and take the box around them.
+        let (min, max) = (object_box.min(), object_box.max());
+        let mut first_box = true;
+        for i in 0..8 {
+            let corner = Vector3::new(
+                if i & 1 == 0 { min.x() } else { max.x() },
+                if i & 2 == 0 { min.y() } else { max.y() },
+                if i & 4 == 0 { min.z() } else { max.z() },
+            );
+            let p = self.object_to_world.transform_point(corner);
+            let corner_box = Aabb::new(p, p);
+            *output_box = if first_box { corner_box } else { Aabb::surrounding_box(output_box, &corner_box) };
+            first_box = false;
+        }
+
+        true
+    }
+
+    fn pdf_value(&self, origin: Vector3, direction: Vector3) -> f64 {
+        // The object's density for the direction mapped into its space, times the change in
+        // solid angle under that mapping: |det A| / |A w|^3 for the linear part A and unit w.
+        let object_direction = self.world_to_object.transform_vector(direction.unit_vector());
+        let length = object_direction.length();
+        let object_pdf = self.object.pdf_value(self.world_to_object.transform_point(origin), object_direction);
+        object_pdf * self.world_to_object.determinant().abs() / (length * length * length)
+    }
+
+    fn random(&self, origin: Vector3, sampler: &mut dyn Sampler) -> Vector3 {
+        let object_direction = self.object.random(self.world_to_object.transform_point(origin), sampler);
+        self.object_to_world.transform_vector(object_direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::HittablePdf;
+    use crate::quad::Quad;
+    use crate::sphere::Sphere;
+    use crate::test_util::assert_consistent_pdf;
+
+    #[test]
+    fn rejects_singular_matrices() {
+        let sphere = Arc::new(Sphere::new(Vector3::new(0.0, 0.0, 0.0), 1.0, None));
+        assert!(Transform::new(sphere.clone(), Matrix4::scaling(Vector3::new(1.0, 0.0, 1.0))).is_none());
+        assert!(Transform::new(sphere, Matrix4::translation(Vector3::new(1.0, 2.0, 3.0)) * Matrix4::rotation_y(15.0)).is_some());
+    }
+
+    #[test]
+    fn light_pdf_is_consistent_under_scaling() {
+        let matrix = Matrix4::translation(Vector3::new(0.3, 0.2, -2.0))
+            * Matrix4::rotation_y(30.0)
+            * Matrix4::scaling(Vector3::new(2.0, 0.5, 1.0));
+        let objects: [Arc<dyn Hittable>; 2] = [
+            Arc::new(Sphere::new(Vector3::new(0.0, 0.0, 0.0), 0.6, None)),
+            Arc::new(Quad::new(Vector3::new(-0.5, -1.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 2.0, 0.5), None)),
+        ];
+        for object in objects {
+            let light = Arc::new(Transform::new(object, matrix).unwrap());
+            assert_consistent_pdf(&HittablePdf::new(light, Vector3::new(0.0, 0.0, 0.0)));
+        }
+    }
+}
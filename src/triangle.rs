@@ -0,0 +1,199 @@
+use std::sync::Arc;
+
+use crate::vector3::Vector3;
+use crate::ray::Ray;
+use crate::hittable::{HitRecord, Hittable, HittableList};
+use crate::material::Material;
+use crate::aabb::Aabb;
+use crate::bvh::BvhNode;
+use crate::sampler::Sampler;
+
+// Vertex attribute buffers shared by every triangle of a mesh.
+#[derive(Clone, Default)]
+pub struct MeshBuffers {
+    pub positions: Vec<Vector3>,
+    pub normals: Vec<Vector3>,
+    pub uvs: Vec<(f64, f64)>,
+}
+
+// Indices of one triangle corner into the position, normal and uv buffers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VertexIndex {
+    pub position: usize,
+    pub normal: Option<usize>,
+    pub uv: Option<usize>,
+}
+
+impl VertexIndex {
+    pub fn new(position: usize) -> Self { VertexIndex { position, normal: None, uv: None } }
+}
+
+pub struct Triangle {
+    buffers: Arc<MeshBuffers>,
+    vertices: [VertexIndex; 3],
+    mat: Option<Arc<dyn Material>>,
+}
+
+impl Triangle {
+    pub fn new(a: Vector3, b: Vector3, c: Vector3, mat: Option<Arc<dyn Material>>) -> Triangle {
+        let buffers = Arc::new(MeshBuffers { positions: vec![a, b, c], ..Default::default() });
+        Triangle::from_mesh(buffers, [VertexIndex::new(0), VertexIndex::new(1), VertexIndex::new(2)], mat)
+    }
+    pub fn from_mesh(buffers: Arc<MeshBuffers>, vertices: [VertexIndex; 3], mat: Option<Arc<dyn Material>>) -> Triangle {
+        Triangle { buffers, vertices, mat }
+    }
+    fn position(&self, i: usize) -> Vector3 { self.buffers.positions[self.vertices[i].position] }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        // Möller–Trumbore: solve for the barycentric coordinates (b1, b2) and t directly.
+        let p0 = self.position(0);
+        let edge1 = self.position(1) - p0;
+        let edge2 = self.position(2) - p0;
+
+        let pvec = r.dir().cross(edge2);
+        let det = edge1.dot(pvec);
+        if det.abs() < 1e-12 {
+            return false;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = r.orig() - p0;
+        let b1 = tvec.dot(pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return false;
+        }
+
+        let qvec = tvec.cross(edge1);
+        let b2 = r.dir().dot(qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return false;
+        }
+
+        let t = edge2.dot(qvec) * inv_det;
+        if t <= t_min || t_max <= t {
+            return false;
+        }
+        let b0 = 1.0 - b1 - b2;
+
+        rec.t = t;
+        rec.p = r.at(t);
+        rec.mat = self.mat.clone();
+        rec.set_face_normal(r, &edge1.cross(edge2).unit_vector());
+
+        // Interpolate per-vertex normals for smooth shading, keeping them on the side the ray hit.
+        if let [Some(n0), Some(n1), Some(n2)] = self.vertices.map(|v| v.normal) {
+            let normals = &self.buffers.normals;
+            let shading_normal = (b0 * normals[n0] + b1 * normals[n1] + b2 * normals[n2]).unit_vector();
+            if !shading_normal.near_zero() {
+                rec.normal = if shading_normal.dot(rec.normal) < 0.0 { -shading_normal } else { shading_normal };
+            }
+        }
+
+        // Without texture coordinates, (u, v) are the barycentric coordinates and dp/du is edge1.
+        let mut tangent = edge1;
+        (rec.u, rec.v) = match self.vertices.map(|v| v.uv) {
+            [Some(t0), Some(t1), Some(t2)] => {
+                let uvs = &self.buffers.uvs;
+                let (du1, dv1) = (uvs[t1].0 - uvs[t0].0, uvs[t1].1 - uvs[t0].1);
+                let (du2, dv2) = (uvs[t2].0 - uvs[t0].0, uvs[t2].1 - uvs[t0].1);
+                let uv_det = du1 * dv2 - dv1 * du2;
+                if uv_det.abs() > 1e-12 {
+                    tangent = (dv2 * edge1 - dv1 * edge2) / uv_det;
+                }
+                (b0 * uvs[t0].0 + b1 * uvs[t1].0 + b2 * uvs[t2].0, b0 * uvs[t0].1 + b1 * uvs[t1].1 + b2 * uvs[t2].1)
+            }
+            _ => (b1, b2),
+        };
+        rec.tangent = if tangent.near_zero() { tangent } else { tangent.unit_vector() };
+
+        true
+    }
+
+    fn bounding_box(&self, _time0: f64, _time1: f64, output_box: &mut Aabb) -> bool {
+        let box01 = Aabb::new(self.position(0), self.position(1));
+        *output_box = Aabb::surrounding_box(&box01, &Aabb::new(self.position(2), self.position(2)));
+        true
+    }
+
+    fn pdf_value(&self, origin: Vector3, direction: Vector3) -> f64 {
+        // Convert the uniform density over the triangle's area into a density over solid angle,
+        // using the geometric normal rather than the interpolated one the hit record may hold.
+        let mut rec = HitRecord::default();
+        if !self.hit(&Ray::new(origin, direction, 0.0), 0.001, f64::INFINITY, &mut rec) {
+            return 0.0;
+        }
+
+        let normal = (self.position(1) - self.position(0)).cross(self.position(2) - self.position(0));
+        let area = 0.5 * normal.length();
+        let distance_squared = rec.t * rec.t * direction.length_squared();
+        let cosine = (direction.dot(normal) / (direction.length() * normal.length())).abs();
+
+        distance_squared / (cosine * area)
+    }
+
+    fn random(&self, origin: Vector3, sampler: &mut dyn Sampler) -> Vector3 {
+        // Points of the unit square past the diagonal are folded back onto the triangle.
+        let (mut s, mut t) = sampler.get_2d();
+        if s + t > 1.0 {
+            (s, t) = (1.0 - s, 1.0 - t);
+        }
+        let p0 = self.position(0);
+        let p = p0 + s * (self.position(1) - p0) + t * (self.position(2) - p0);
+        p - origin
+    }
+}
+
+pub struct TriangleMesh {
+    buffers: Arc<MeshBuffers>,
+    triangles: Option<BvhNode>,
+}
+
+impl TriangleMesh {
+    pub fn new(buffers: Arc<MeshBuffers>, faces: &[[VertexIndex; 3]], mat: Option<Arc<dyn Material>>) -> TriangleMesh {
+        let mut list = HittableList::new();
+        for face in faces {
+            list.push(Triangle::from_mesh(buffers.clone(), *face, mat.clone()));
+        }
+        let triangles = BvhNode::new(&list, 0.0, 0.0);
+        TriangleMesh { buffers, triangles }
+    }
+    pub fn buffers(&self) -> &Arc<MeshBuffers> { &self.buffers }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        match &self.triangles {
+            Some(triangles) => triangles.hit(r, t_min, t_max, rec),
+            None => false,
+        }
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64, output_box: &mut Aabb) -> bool {
+        match &self.triangles {
+            Some(triangles) => triangles.bounding_box(time0, time1, output_box),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pdf::HittablePdf;
+    use crate::test_util::assert_consistent_pdf;
+
+    #[test]
+    fn light_pdf_is_consistent() {
+        // Vertex normals that lean away from the face must not change the density.
+        let buffers = MeshBuffers {
+            positions: vec![Vector3::new(-1.0, -1.0, -1.5), Vector3::new(1.5, -0.5, -1.0), Vector3::new(0.0, 1.0, -2.0)],
+            normals: vec![Vector3::new(1.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.2), Vector3::new(-1.0, 0.0, 1.0)],
+            uvs: Vec::new(),
+        };
+        let vertices = [0, 1, 2].map(|i| VertexIndex { position: i, normal: Some(i), uv: None });
+        let triangle = Arc::new(Triangle::from_mesh(Arc::new(buffers), vertices, None));
+        assert_consistent_pdf(&HittablePdf::new(triangle, Vector3::new(0.0, 0.0, 0.0)));
+    }
+}
@@ -1,8 +1,8 @@
-use std::ops::{Add, Div, Mul, Sub};
+use std::ops::{Add, Div, Index, Mul, Neg, Sub};
 
-use crate::random_double;
+use crate::rng::Rng;
 
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq)]
 pub struct Vector3 {
     x: f64,
     y: f64,
@@ -25,30 +25,30 @@ impl Vector3 {
     pub fn length_squared(&self) -> f64 { self.dot(*self) }
     pub fn length(&self) -> f64 { self.length_squared().sqrt() }
     pub fn unit_vector(&self) -> Vector3 { *self / self.length() }
-    pub fn random(min: f64, max: f64) -> Vector3 { Vector3::new(random_double(min, max), random_double(min, max), random_double(min, max)) }
-    pub fn random_in_unit_disk() -> Vector3 {
+    pub fn random(rng: &mut Rng, min: f64, max: f64) -> Vector3 { Vector3::new(rng.random_double(min, max), rng.random_double(min, max), rng.random_double(min, max)) }
+    pub fn random_in_unit_disk(rng: &mut Rng) -> Vector3 {
         loop {
-            let p = Vector3::new(random_double(-1.0, 1.0), random_double(-1.0, 1.0), 0.0);
+            let p = Vector3::new(rng.random_double(-1.0, 1.0), rng.random_double(-1.0, 1.0), 0.0);
             if p.length_squared() < 1.0 { return p }
         }
     }
-    pub fn random_in_unit_sphere() -> Vector3 {
+    pub fn random_in_unit_sphere(rng: &mut Rng) -> Vector3 {
         loop {
-            let p = Vector3::random(-1.0, 1.0);
+            let p = Vector3::random(rng, -1.0, 1.0);
             if p.length_squared() < 1.0 { return p }
         }
     }
-    pub fn random_unit_vector() -> Vector3 { Vector3::random_in_unit_sphere().unit_vector() }
-    pub fn random_on_hemisphere(normal: Vector3) -> Vector3 {
-        let p = Vector3::random_unit_vector();
-        if p.dot(normal) > 0.0 { p } else { -1.0 * p }
+    pub fn random_unit_vector(rng: &mut Rng) -> Vector3 { Vector3::random_in_unit_sphere(rng).unit_vector() }
+    pub fn random_on_hemisphere(rng: &mut Rng, normal: Vector3) -> Vector3 {
+        let p = Vector3::random_unit_vector(rng);
+        if p.dot(normal) > 0.0 { p } else { -p }
     }
     pub fn near_zero(&self) -> bool { self.x.abs() < 1e-8 && self.y.abs() < 1e-8 && self.z.abs() < 1e-8 }
     pub fn reflect(&self, n: Vector3) -> Vector3 { *self - 2.0*self.dot(n)*n }
     pub fn refract(&self, n: Vector3, etai_over_etat: f64) -> Vector3 {
-        let cos_theta = f64::min(-1.0 * self.dot(n), 1.0);
+        let cos_theta = f64::min(-self.dot(n), 1.0);
         let r_out_perp = etai_over_etat * (*self + cos_theta*n);
-        let r_out_parallel = (1.0 - r_out_perp.length_squared()).abs().sqrt() * (-1.0 * n);
+        let r_out_parallel = (1.0 - r_out_perp.length_squared()).abs().sqrt() * -n;
         r_out_perp + r_out_parallel
     }
 }
@@ -68,6 +68,17 @@ impl Add for Vector3 {
     }
 }
 
+impl Neg for Vector3 {
+    type Output = Vector3;
+    fn neg(self) -> Vector3 {
+        Vector3 {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+        }
+    }
+}
+
 impl Sub for Vector3 {
     type Output = Vector3;
     fn sub(self, other: Vector3) -> Vector3 {
@@ -117,4 +128,16 @@ impl Div<f64> for Vector3 {
             z: self.z / scalar,
         }
     }
+}
+
+impl Index<usize> for Vector3 {
+    type Output = f64;
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vector3 axis out of range: {}", axis),
+        }
+    }
 }
\ No newline at end of file